@@ -53,7 +53,7 @@ fn main() -> Result<()> {
     // Perform HDBSCAN clustering
     let min_cluster_size = 10;
     let min_samples = 5;
-    let result = hdbscan_clustering(&data, min_cluster_size, min_samples, None, None)?;
+    let result = hdbscan_clustering(&data, min_cluster_size, Some(min_samples), None, None)?;
     
     println!("========= Clustering Report =========");
     println!("Total points: {}", data.len());
@@ -1,13 +1,25 @@
 use anyhow::{anyhow, Result};
-use ndarray::Array2;
-use petal_clustering::{Fit as PetalFit, HDbscan};
-use petal_neighbors::distance::Euclidean;
+use ndarray::{Array1, Array2, ArrayView1, ArrayView2, Axis};
+use petal_clustering::{Dbscan, Fit as PetalFit, HDbscan};
+use petal_neighbors::distance::Metric;
+use serde::Serialize;
 use std::collections::HashMap;
 use linfa::prelude::*;
 use linfa::DatasetBase;
 use linfa_clustering::{GaussianMixtureModel, GmmValidParams, KMeans};
 use rand_xoshiro::Xoshiro256Plus;
-use rand::SeedableRng;
+use rand::{Rng, SeedableRng};
+
+use crate::hierarchy::{
+    agglomerative_dendrogram, condense_tree, cut_dendrogram, cut_dendrogram_at_height, linkage,
+    single_linkage_tree, DendrogramCut, Linkage,
+};
+use crate::metrics::outlier_fraction;
+use crate::neighbors::k_distances;
+use crate::utils::{
+    drop_dims, euclidean_distance, flat_view, flatten_rows, mean_of_rows, pairwise_distances, sparse_dot,
+    validate_row_lengths, DistanceMetric, Normalized, SparseVector,
+};
 
 /// Result of a clustering operation
 #[derive(Debug, Clone)]
@@ -18,213 +30,3220 @@ pub struct ClusteringResult {
     pub outliers: Vec<usize>,
     /// Flattened representation of cluster assignments (index = data point, value = cluster ID)
     pub assignments: Vec<usize>,
+    /// Pairs of cluster IDs whose centroids collapsed onto (nearly) the same point; only
+    /// K-means populates this, and a non-empty list means `n_clusters` is too high for the data
+    pub degenerate_centroids: Vec<(usize, usize)>,
+    /// Centroid of every cluster, indexed by cluster ID, for the algorithms that fit one
+    /// (KMeans and its variants, the GMM component means, the K-medoids medoids); `None` for
+    /// density-based and hierarchical results and for results built from memberships alone
+    pub centroids: Option<Vec<Vec<f64>>>,
+}
+
+impl ClusteringResult {
+    /// Builds a consistent clustering result from cluster memberships
+    ///
+    /// `assignments` is derived from `clusters`, with outliers assigned 0.
+    ///
+    /// # Arguments
+    /// * `clusters` - Mapping of cluster IDs to the indices of data points in that cluster
+    /// * `outliers` - Indices of data points considered as outliers
+    /// * `n_points` - Total number of data points
+    pub fn from_clusters(clusters: HashMap<usize, Vec<usize>>, outliers: Vec<usize>, n_points: usize) -> Self {
+        let mut assignments = vec![0; n_points];
+        for (&cluster_id, indices) in clusters.iter() {
+            for &idx in indices {
+                assignments[idx] = cluster_id;
+            }
+        }
+        
+        ClusteringResult {
+            clusters,
+            outliers,
+            assignments,
+            degenerate_centroids: Vec::new(),
+            centroids: None,
+        }
+    }
+    
+    /// Cluster label of every data point, with `None` for outliers
+    ///
+    /// Unlike `assignments`, this distinguishes outliers from points in cluster 0.
+    ///
+    /// # Returns
+    /// * `Vec<Option<usize>>` - Cluster ID per data point, or `None` if the point is an outlier
+    pub fn labels(&self) -> Vec<Option<usize>> {
+        let mut labels = vec![None; self.assignments.len()];
+        for (&cluster_id, indices) in self.clusters.iter() {
+            for &idx in indices {
+                labels[idx] = Some(cluster_id);
+            }
+        }
+        labels
+    }
+    
+    /// Cluster labels in the scikit-learn / Python `hdbscan` convention
+    ///
+    /// Outliers are labeled -1 and clusters are renumbered 0, 1, 2, ... in ascending order of
+    /// their cluster ID.
+    ///
+    /// # Returns
+    /// * `Vec<i32>` - Label per data point
+    pub fn labels_sklearn(&self) -> Vec<i32> {
+        let mut ids: Vec<usize> = self.clusters.keys().cloned().collect();
+        ids.sort_unstable();
+        let renumbered: HashMap<usize, i32> = ids.into_iter().enumerate().map(|(i, id)| (id, i as i32)).collect();
+        
+        self.labels()
+            .into_iter()
+            .map(|label| label.map_or(-1, |id| renumbered[&id]))
+            .collect()
+    }
+    
+    /// Returns the clusters in a deterministic order
+    ///
+    /// `clusters` is a `HashMap` whose iteration order changes between runs; this is the same
+    /// content sorted for serialization, snapshot tests and cross-run comparison.
+    ///
+    /// # Returns
+    /// * `Vec<(usize, Vec<usize>)>` - `(cluster_id, members)` pairs sorted by cluster ID, with
+    ///   the member indices sorted ascending
+    pub fn clusters_sorted(&self) -> Vec<(usize, Vec<usize>)> {
+        let mut clusters: Vec<(usize, Vec<usize>)> = self
+            .clusters
+            .iter()
+            .map(|(&cluster_id, members)| {
+                let mut members = members.clone();
+                members.sort_unstable();
+                (cluster_id, members)
+            })
+            .collect();
+        clusters.sort_unstable_by_key(|(cluster_id, _)| *cluster_id);
+        clusters
+    }
+    
+    /// Run-length encodes `assignments`, compact for data sorted by cluster
+    ///
+    /// `decode_assignments_rle` restores `assignments` exactly. Outliers are encoded with their
+    /// assignment 0, so the outlier list has to be stored separately if it matters.
+    ///
+    /// # Returns
+    /// * `Vec<(usize, usize)>` - `(cluster_id, run_length)` pairs in point order
+    pub fn assignments_rle(&self) -> Vec<(usize, usize)> {
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for &cluster_id in self.assignments.iter() {
+            match runs.last_mut() {
+                Some((last, length)) if *last == cluster_id => *length += 1,
+                _ => runs.push((cluster_id, 1)),
+            }
+        }
+        runs
+    }
+    
+    /// Checks that `clusters`, `outliers` and `assignments` agree with each other
+    ///
+    /// Every point index must appear exactly once across the cluster member lists and the
+    /// outliers, every cluster member must have that cluster as its assignment, and every
+    /// outlier must have assignment 0. Useful after building or transforming a result by hand.
+    ///
+    /// # Returns
+    /// * `Result<(), ClusterError>` - `Ok` if consistent, otherwise the first inconsistency found
+    pub fn validate(&self) -> std::result::Result<(), ClusterError> {
+        let n_points = self.assignments.len();
+        let mut seen = vec![false; n_points];
+        let mut visit = |index: usize| -> std::result::Result<(), ClusterError> {
+            match seen.get_mut(index) {
+                None => Err(ClusterError::IndexOutOfRange { index, n_points }),
+                Some(true) => Err(ClusterError::DuplicateIndex { index }),
+                Some(flag) => {
+                    *flag = true;
+                    Ok(())
+                }
+            }
+        };
+        
+        let mut cluster_ids: Vec<usize> = self.clusters.keys().cloned().collect();
+        cluster_ids.sort_unstable();
+        for cluster_id in cluster_ids {
+            for &index in self.clusters[&cluster_id].iter() {
+                visit(index)?;
+                if self.assignments[index] != cluster_id {
+                    return Err(ClusterError::AssignmentMismatch {
+                        index,
+                        cluster: cluster_id,
+                        assignment: self.assignments[index],
+                    });
+                }
+            }
+        }
+        for &index in self.outliers.iter() {
+            visit(index)?;
+            if self.assignments[index] != 0 {
+                return Err(ClusterError::OutlierAssignment {
+                    index,
+                    assignment: self.assignments[index],
+                });
+            }
+        }
+        
+        match seen.iter().position(|&s| !s) {
+            Some(index) => Err(ClusterError::MissingIndex { index }),
+            None => Ok(()),
+        }
+    }
+    
+    /// Summarizes the clustering as a compact, serializable record, e.g. for metrics logging
+    ///
+    /// # Returns
+    /// * `ClusteringSummary` - Counts, cluster size extremes and balance of the clustering
+    pub fn summary(&self) -> ClusteringSummary {
+        let sizes: Vec<usize> = self.clusters.values().map(|indices| indices.len()).collect();
+        let n_clustered: usize = sizes.iter().sum();
+        
+        let balance_entropy = if sizes.len() < 2 {
+            1.0
+        } else {
+            let entropy: f64 = sizes
+                .iter()
+                .filter(|&&size| size > 0)
+                .map(|&size| {
+                    let p = size as f64 / n_clustered as f64;
+                    -p * p.ln()
+                })
+                .sum();
+            entropy / (sizes.len() as f64).ln()
+        };
+        
+        ClusteringSummary {
+            n_points: self.assignments.len(),
+            n_clusters: self.clusters.len(),
+            n_outliers: self.outliers.len(),
+            outlier_fraction: outlier_fraction(self),
+            largest_cluster_size: sizes.iter().cloned().max().unwrap_or(0),
+            smallest_cluster_size: sizes.iter().cloned().min().unwrap_or(0),
+            balance_entropy,
+        }
+    }
+}
+
+/// Inconsistency found by `ClusteringResult::validate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClusterError {
+    /// A cluster or the outliers contain an index outside of `0..n_points`
+    IndexOutOfRange { index: usize, n_points: usize },
+    /// A point appears more than once across the clusters and outliers
+    DuplicateIndex { index: usize },
+    /// A point appears in no cluster and not among the outliers
+    MissingIndex { index: usize },
+    /// A cluster member's assignment names a different cluster
+    AssignmentMismatch { index: usize, cluster: usize, assignment: usize },
+    /// An outlier's assignment is not 0
+    OutlierAssignment { index: usize, assignment: usize },
+}
+
+impl std::fmt::Display for ClusterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClusterError::IndexOutOfRange { index, n_points } => {
+                write!(f, "Point index {} is out of range for {} points", index, n_points)
+            }
+            ClusterError::DuplicateIndex { index } => {
+                write!(f, "Point {} appears more than once in clusters and outliers", index)
+            }
+            ClusterError::MissingIndex { index } => {
+                write!(f, "Point {} is in no cluster and not an outlier", index)
+            }
+            ClusterError::AssignmentMismatch { index, cluster, assignment } => write!(
+                f,
+                "Point {} is a member of cluster {} but assigned to {}",
+                index, cluster, assignment
+            ),
+            ClusterError::OutlierAssignment { index, assignment } => {
+                write!(f, "Outlier {} has assignment {}, expected 0", index, assignment)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClusterError {}
+
+/// Compact statistics of a clustering result, see `ClusteringResult::summary`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ClusteringSummary {
+    /// Total number of data points
+    pub n_points: usize,
+    /// Number of clusters, excluding outliers
+    pub n_clusters: usize,
+    /// Number of outliers
+    pub n_outliers: usize,
+    /// `n_outliers / n_points` (0.0 when there are no points)
+    pub outlier_fraction: f64,
+    /// Size of the largest cluster (0 when there are no clusters)
+    pub largest_cluster_size: usize,
+    /// Size of the smallest cluster (0 when there are no clusters)
+    pub smallest_cluster_size: usize,
+    /// Shannon entropy of the cluster size distribution divided by its maximum `ln(n_clusters)`:
+    /// 1.0 for equally sized clusters, near 0 when one cluster holds almost every point. 1.0
+    /// with fewer than two clusters
+    pub balance_entropy: f64,
+}
+
+/// Decodes assignments encoded with `ClusteringResult::assignments_rle`
+///
+/// # Arguments
+/// * `runs` - `(cluster_id, run_length)` pairs in point order
+///
+/// # Returns
+/// * `Vec<usize>` - The cluster assignment of every point
+pub fn decode_assignments_rle(runs: &[(usize, usize)]) -> Vec<usize> {
+    runs.iter()
+        .flat_map(|&(cluster_id, length)| std::iter::repeat_n(cluster_id, length))
+        .collect()
+}
+
+/// Clustering algorithm and parameters, for functions that run clustering internally
+///
+/// Parameters not listed here use the defaults of the corresponding clustering function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    /// HDBSCAN clustering (see `hdbscan_clustering`)
+    Hdbscan {
+        min_cluster_size: usize,
+        min_samples: Option<usize>,
+        metric: DistanceMetric,
+    },
+    /// Gaussian Mixture Model clustering (see `gmm_clustering`)
+    Gmm {
+        n_clusters: usize,
+        seed: Option<u64>,
+    },
+    /// K-means clustering (see `kmeans_clustering`)
+    KMeans {
+        n_clusters: usize,
+        seed: Option<u64>,
+    },
+}
+
+impl Algorithm {
+    /// Distance metric the algorithm clusters with
+    ///
+    /// GMM and K-means always work in Euclidean space.
+    pub fn metric(&self) -> DistanceMetric {
+        match *self {
+            Algorithm::Hdbscan { metric, .. } => metric,
+            Algorithm::Gmm { .. } | Algorithm::KMeans { .. } => DistanceMetric::Euclidean,
+        }
+    }
+}
+
+/// Runs the given clustering algorithm on a dataset
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `algo` - The algorithm and its parameters
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result or error
+pub fn run_clustering(data: &[Vec<f64>], algo: Algorithm) -> Result<ClusteringResult> {
+    match algo {
+        Algorithm::Hdbscan { min_cluster_size, min_samples, metric } => {
+            let params = HdbscanParams {
+                min_samples,
+                metric,
+                ..Default::default()
+            };
+            hdbscan_clustering_with_params(data, min_cluster_size, params)
+        }
+        Algorithm::Gmm { n_clusters, seed } => gmm_clustering(data, n_clusters, None, None, seed),
+        Algorithm::KMeans { n_clusters, seed } => kmeans_clustering(data, n_clusters, None, None, seed),
+    }
+}
+
+/// Runs the given clustering algorithm on a dataset, ignoring some of its columns
+///
+/// The listed columns (e.g. identifiers accidentally left in the feature matrix) are removed
+/// with `drop_dims` before clustering, so they cannot distort the distances.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `algo` - The algorithm and its parameters
+/// * `ignore_dims` - Optional column indices to exclude (default: use every column)
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result, or error if an index is out of range
+pub fn run_clustering_ignoring_dims(
+    data: &[Vec<f64>],
+    algo: Algorithm,
+    ignore_dims: Option<&[usize]>,
+) -> Result<ClusteringResult> {
+    match ignore_dims {
+        Some(dims) => run_clustering(&drop_dims(data, dims)?, algo),
+        None => run_clustering(data, algo),
+    }
+}
+
+/// Runs the given clustering algorithm on the rows selected by a boolean mask
+///
+/// Only rows whose mask entry is `true` are clustered; the result is mapped back so that all
+/// indices refer to the full dataset, and masked-out rows are reported as outliers. Since
+/// outliers have assignment 0, use `ClusteringResult::labels` to tell them apart from
+/// cluster 0 with algorithms that produce it (GMM, KMeans).
+///
+/// # Arguments
+/// * `data` - A 2D array of data points
+/// * `algo` - The algorithm and its parameters
+/// * `mask` - One flag per data point; `true` rows are clustered
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result over all rows, or error if the mask
+///   length differs from the number of data points or clustering fails
+pub fn cluster_subset(data: &[Vec<f64>], algo: Algorithm, mask: &[bool]) -> Result<ClusteringResult> {
+    if mask.len() != data.len() {
+        return Err(anyhow!("Mask has {} entries but data has {} points", mask.len(), data.len()));
+    }
+    
+    let selected: Vec<usize> = (0..data.len()).filter(|&idx| mask[idx]).collect();
+    let subset: Vec<Vec<f64>> = selected.iter().map(|&idx| data[idx].clone()).collect();
+    let sub_result = run_clustering(&subset, algo)?;
+    remap_to_original(&sub_result, &selected, data.len())
+}
+
+/// Maps a clustering of sampled points back to the index space of the full dataset
+///
+/// Use this after clustering an `EmbeddingResult` built from a sample: its
+/// `original_indices` say which input point each embedded point came from. Points that were
+/// not sampled are reported as outliers. Centroids and degenerate centroid pairs are kept,
+/// since they do not refer to point indices.
+///
+/// # Arguments
+/// * `result` - Clustering of the sampled points
+/// * `original_indices` - Index in the full dataset of each sampled point
+/// * `total_points` - Number of points in the full dataset
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering over all `total_points`, or error if
+///   `original_indices` does not have one entry per sampled point, repeats an index or
+///   contains one that is out of range
+pub fn remap_to_original(
+    result: &ClusteringResult,
+    original_indices: &[usize],
+    total_points: usize,
+) -> Result<ClusteringResult> {
+    if original_indices.len() != result.assignments.len() {
+        return Err(anyhow!(
+            "Got {} original indices for {} clustered points",
+            original_indices.len(),
+            result.assignments.len()
+        ));
+    }
+    let mut sampled = vec![false; total_points];
+    for &idx in original_indices {
+        if idx >= total_points {
+            return Err(anyhow!("Original index {} is out of range for {} points", idx, total_points));
+        }
+        if std::mem::replace(&mut sampled[idx], true) {
+            return Err(anyhow!("Original index {} appears more than once", idx));
+        }
+    }
+    
+    let clusters: HashMap<usize, Vec<usize>> = result
+        .clusters
+        .iter()
+        .map(|(&cluster_id, indices)| (cluster_id, indices.iter().map(|&i| original_indices[i]).collect()))
+        .collect();
+    let mut outliers: Vec<usize> = result.outliers.iter().map(|&i| original_indices[i]).collect();
+    outliers.extend((0..total_points).filter(|&idx| !sampled[idx]));
+    outliers.sort_unstable();
+    
+    let mut remapped = ClusteringResult::from_clusters(clusters, outliers, total_points);
+    remapped.degenerate_centroids = result.degenerate_centroids.clone();
+    remapped.centroids = result.centroids.clone();
+    Ok(remapped)
+}
+
+/// Optional parameters of HDBSCAN clustering
+///
+/// Override single fields with `HdbscanParams { metric: DistanceMetric::Cosine, ..Default::default() }`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HdbscanParams {
+    /// Minimum number of neighbors required for a point to be considered a core point
+    /// (default: `min_cluster_size`, matching the Python `hdbscan` library). Larger values make
+    /// the density estimate more conservative, so more points are declared outliers
+    pub min_samples: Option<usize>,
+    /// Neighborhood radius forwarded to the underlying HDBSCAN implementation (default:
+    /// 0.0001). It does not merge clusters; see `cluster_selection_epsilon`. Unused with
+    /// Cosine and DTW, see `metric`
+    pub epsilon: Option<f64>,
+    /// Distance scale below which clusters are not split (default: none, matching the Python
+    /// `hdbscan` library's 0.0). Clusters joined by a path of mutual-reachability edges
+    /// shorter than this are merged into one, which absorbs micro-clusters that pass
+    /// `min_cluster_size` but sit within this distance of each other. `min_cluster_size` still
+    /// decides which groups count as clusters at all; the epsilon only merges clusters HDBSCAN
+    /// already selected, and outliers stay outliers. Setting it costs an extra O(N²) minimum
+    /// spanning tree (see `hdbscan_mst`)
+    pub cluster_selection_epsilon: Option<f64>,
+    /// Factor for determining cluster prominence (default: 1.0). Only 1.0 is supported with
+    /// Cosine and DTW, see `metric`
+    pub alpha: Option<f64>,
+    /// Optional cap on the number of clusters, applied after `cluster_selection_epsilon`. When
+    /// HDBSCAN finds more, the two clusters with the closest centroids are merged repeatedly
+    /// until the cap is met. This is a heuristic post-merge, not a change to HDBSCAN's own
+    /// cluster selection (default: none)
+    pub max_clusters: Option<usize>,
+    /// The distance metric for the density estimate, the cluster selection epsilon and the
+    /// centroid merges (default: `DistanceMetric::Euclidean`). petal-clustering finds the core
+    /// distances with a ball tree, which needs the triangle inequality, so it is used for
+    /// Euclidean and Manhattan only. Cosine and DTW violate it and are clustered by brute
+    /// force instead: exact core distances, the mutual reachability MST of `hdbscan_mst` and
+    /// excess-of-mass selection on its condensed tree (see `CondensedTree::select_clusters`),
+    /// at O(N²) time and O(N) memory
+    pub metric: DistanceMetric,
 }
 
 /// Performs HDBSCAN clustering on a dataset
 ///
+/// Uses the defaults of `HdbscanParams` for everything but the listed parameters; see
+/// `hdbscan_clustering_with_params` for the cluster selection epsilon, the cluster cap and
+/// other distance metrics.
+///
 /// # Arguments
 /// * `data` - A 2D array of data points to cluster
 /// * `min_cluster_size` - Minimum number of points to form a cluster
 /// * `min_samples` - Minimum number of neighbors required for a point to be considered a core point
-/// * `epsilon` - Distance threshold for connecting points (default: 0.0001)
+///   (default: `min_cluster_size`)
+/// * `epsilon` - Neighborhood radius forwarded to the underlying HDBSCAN implementation
+///   (default: 0.0001)
 /// * `alpha` - Factor for determining cluster prominence (default: 1.0)
 ///
 /// # Returns
 /// * `Result<ClusteringResult>` - The clustering result or error
-pub fn hdbscan_clustering(
-    data: &[Vec<f64>],
-    min_cluster_size: usize,
-    min_samples: usize,
-    epsilon: Option<f64>,
-    alpha: Option<f64>,
+pub fn hdbscan_clustering(
+    data: &[Vec<f64>],
+    min_cluster_size: usize,
+    min_samples: Option<usize>,
+    epsilon: Option<f64>,
+    alpha: Option<f64>,
+) -> Result<ClusteringResult> {
+    let params = HdbscanParams {
+        min_samples,
+        epsilon,
+        alpha,
+        ..Default::default()
+    };
+    hdbscan_clustering_with_params(data, min_cluster_size, params)
+}
+
+/// Performs HDBSCAN clustering on a dataset with every optional parameter
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `min_cluster_size` - Minimum number of points to form a cluster
+/// * `params` - The optional parameters (see `HdbscanParams`)
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result or error
+pub fn hdbscan_clustering_with_params(
+    data: &[Vec<f64>],
+    min_cluster_size: usize,
+    params: HdbscanParams,
+) -> Result<ClusteringResult> {
+    // Convert data to ndarray format
+    let nrows = data.len();
+    if nrows == 0 {
+        return Err(anyhow!("Empty input data"));
+    }
+    
+    let ncols = validate_row_lengths(data)?;
+    let flat_data = flatten_rows(data);
+    
+    let data_array = Array2::from_shape_vec((nrows, ncols), flat_data)
+        .map_err(|e| anyhow!("Failed to reshape data: {}", e))?;
+    
+    hdbscan_on_array(data_array.view(), min_cluster_size, params)
+}
+
+/// Same as `hdbscan_clustering_with_params`, on a flat row-major buffer instead of one `Vec`
+/// per point
+///
+/// Clusters the buffer in place, without collecting it into a new array first.
+///
+/// # Arguments
+/// * `data` - The data points, row after row
+/// * `n_rows` - Number of data points
+/// * `n_cols` - Number of dimensions per data point
+/// * `min_cluster_size` - Minimum number of points to form a cluster
+/// * `params` - The optional parameters (see `HdbscanParams`)
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result, or error if the buffer length is not
+///   `n_rows * n_cols`
+pub fn hdbscan_clustering_flat(
+    data: &[f64],
+    n_rows: usize,
+    n_cols: usize,
+    min_cluster_size: usize,
+    params: HdbscanParams,
+) -> Result<ClusteringResult> {
+    hdbscan_on_array(flat_view(data, n_rows, n_cols)?, min_cluster_size, params)
+}
+
+/// HDBSCAN on a non-empty 2D array, shared by the nested and flat entry points
+fn hdbscan_on_array(
+    data_array: ArrayView2<f64>,
+    min_cluster_size: usize,
+    params: HdbscanParams,
+) -> Result<ClusteringResult> {
+    let HdbscanParams {
+        min_samples,
+        epsilon,
+        cluster_selection_epsilon,
+        alpha,
+        max_clusters,
+        metric,
+    } = params;
+    let nrows = data_array.nrows();
+    if let Some(eps) = cluster_selection_epsilon {
+        if eps.is_nan() || eps < 0.0 {
+            return Err(anyhow!("cluster_selection_epsilon must be non-negative, got {}", eps));
+        }
+    }
+    
+    let min_samples = min_samples.unwrap_or(min_cluster_size);
+    let rows = || -> Vec<Vec<f64>> { data_array.outer_iter().map(|row| row.to_vec()).collect() };
+    
+    let (mut clusters, outliers) = if metric.is_metric() {
+        // Create HDBSCAN algorithm with parameters
+        let mut hdbscan = HDbscan {
+            eps: epsilon.unwrap_or(0.0001),
+            alpha: alpha.unwrap_or(1.0),
+            min_samples,
+            min_cluster_size,
+            metric: PetalMetric(metric),
+            boruvka: true,
+        };
+        PetalFit::fit(&mut hdbscan, &data_array)
+    } else {
+        if alpha.is_some_and(|alpha| alpha != 1.0) {
+            return Err(anyhow!("alpha must be 1.0 for {:?}, which is clustered by brute force", metric));
+        }
+        let result = brute_force_hdbscan(&rows(), min_cluster_size, min_samples, metric)?;
+        (result.clusters, result.outliers)
+    };
+    
+    if let Some(eps) = cluster_selection_epsilon.filter(|&eps| eps > 0.0 && clusters.len() > 1) {
+        let mst = mutual_reachability_mst(&rows(), min_samples.min(nrows), metric)?;
+        clusters = merge_clusters_within(clusters, &mst, eps, nrows);
+    }
+    
+    if let Some(max_clusters) = max_clusters {
+        let mut centroids: HashMap<usize, Vec<f64>> = clusters
+            .iter()
+            .map(|(&cluster_id, indices)| {
+                let centroid = data_array.select(Axis(0), indices).mean_axis(Axis(0));
+                (cluster_id, centroid.expect("HDBSCAN clusters are non-empty").to_vec())
+            })
+            .collect();
+        merge_closest_centroids(&mut clusters, &mut centroids, metric, |n_clusters, _| {
+            n_clusters > max_clusters.max(1)
+        });
+    }
+    
+    // Create cluster assignments vector (0 is reserved for outliers)
+    let mut assignments = vec![0; nrows];
+    for (cluster_id, indices) in clusters.iter() {
+        for &idx in indices {
+            assignments[idx] = *cluster_id;
+        }
+    }
+    
+    Ok(ClusteringResult {
+        clusters,
+        outliers,
+        assignments,
+        degenerate_centroids: Vec::new(),
+        centroids: None,
+    })
+}
+
+/// HDBSCAN without spatial indexes, for distances that are not true metrics
+///
+/// Cluster IDs start at 1, in order of each cluster's smallest point.
+fn brute_force_hdbscan(
+    data: &[Vec<f64>],
+    min_cluster_size: usize,
+    min_samples: usize,
+    metric: DistanceMetric,
+) -> Result<ClusteringResult> {
+    let n = data.len();
+    if n < 2 {
+        return Ok(ClusteringResult::from_clusters(HashMap::new(), (0..n).collect(), n));
+    }
+    
+    let mst = mutual_reachability_mst(data, min_samples.min(n), metric)?;
+    let tree = condense_tree(&single_linkage_tree(&mst, n)?, n, min_cluster_size)?;
+    let clusters: HashMap<usize, Vec<usize>> = tree
+        .select_clusters()
+        .into_iter()
+        .enumerate()
+        .map(|(i, points)| (i + 1, points))
+        .collect();
+    
+    let mut clustered = vec![false; n];
+    clusters.values().flatten().for_each(|&idx| clustered[idx] = true);
+    let outliers = (0..n).filter(|&idx| !clustered[idx]).collect();
+    Ok(ClusteringResult::from_clusters(clusters, outliers, n))
+}
+
+/// Adapts the crate's `DistanceMetric` to the metric trait of petal-neighbors
+#[derive(Debug, Clone, Copy)]
+struct PetalMetric(DistanceMetric);
+
+impl Metric<f64> for PetalMetric {
+    fn distance(&self, x1: &ArrayView1<f64>, x2: &ArrayView1<f64>) -> f64 {
+        match (x1.as_slice(), x2.as_slice()) {
+            (Some(a), Some(b)) => self.0.distance(a, b),
+            _ => self.0.distance(&x1.to_vec(), &x2.to_vec()),
+        }
+    }
+    
+    fn rdistance(&self, x1: &ArrayView1<f64>, x2: &ArrayView1<f64>) -> f64 {
+        self.distance(x1, x2)
+    }
+    
+    fn rdistance_to_distance(&self, d: f64) -> f64 {
+        d
+    }
+    
+    fn distance_to_rdistance(&self, d: f64) -> f64 {
+        d
+    }
+}
+
+/// Merges clusters that are connected by MST edges shorter than `eps`
+///
+/// Points joined by such edges form the connected components of the graph at distance `eps`;
+/// all clusters with members in the same component become one cluster, which keeps the
+/// smallest of their IDs.
+fn merge_clusters_within(
+    clusters: HashMap<usize, Vec<usize>>,
+    mst: &[(usize, usize, f64)],
+    eps: f64,
+    n_points: usize,
+) -> HashMap<usize, Vec<usize>> {
+    fn find(parent: &mut [usize], mut x: usize) -> usize {
+        while parent[x] != x {
+            parent[x] = parent[parent[x]];
+            x = parent[x];
+        }
+        x
+    }
+    
+    // Components of the points at distance eps
+    let mut component: Vec<usize> = (0..n_points).collect();
+    for &(a, b, _) in mst.iter().filter(|&&(_, _, weight)| weight < eps) {
+        let (ra, rb) = (find(&mut component, a), find(&mut component, b));
+        component[ra] = rb;
+    }
+    
+    // The smallest cluster ID in every component; a cluster spanning several components
+    // links them all
+    let mut ids: Vec<usize> = clusters.keys().cloned().collect();
+    ids.sort_unstable();
+    let mut root_of: HashMap<usize, usize> = ids.iter().map(|&id| (id, id)).collect();
+    let mut cluster_of_component: HashMap<usize, usize> = HashMap::new();
+    for &cluster_id in &ids {
+        for &idx in &clusters[&cluster_id] {
+            let comp = find(&mut component, idx);
+            let other = *cluster_of_component.entry(comp).or_insert(cluster_id);
+            let (ra, rb) = (root_of[&cluster_id], root_of[&other]);
+            if ra != rb {
+                let (keep, drop) = (ra.min(rb), ra.max(rb));
+                root_of.values_mut().filter(|root| **root == drop).for_each(|root| *root = keep);
+            }
+        }
+    }
+    
+    let mut merged: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (cluster_id, indices) in clusters {
+        merged.entry(root_of[&cluster_id]).or_default().extend(indices);
+    }
+    for indices in merged.values_mut() {
+        indices.sort_unstable();
+    }
+    merged
+}
+
+/// Computes the minimum spanning tree of the mutual-reachability graph that HDBSCAN builds
+///
+/// The mutual-reachability distance between two points is the maximum of their Euclidean
+/// distance and both of their core distances, where a point's core distance is the distance
+/// to its `min_samples`-th nearest neighbor (counting the point itself). Removing MST edges
+/// from heaviest to lightest yields the HDBSCAN cluster hierarchy, so the edges can be used
+/// to draw single-linkage dendrograms or connectivity plots.
+///
+/// Runs Prim's algorithm on the complete graph: O(N²) time and O(N) memory.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points
+/// * `min_samples` - Neighborhood size used for the core distances
+///
+/// # Returns
+/// * `Result<Vec<(usize, usize, f64)>>` - The N - 1 MST edges as `(point_a, point_b, weight)`,
+///   sorted by ascending weight, or error
+pub fn hdbscan_mst(data: &[Vec<f64>], min_samples: usize) -> Result<Vec<(usize, usize, f64)>> {
+    mutual_reachability_mst(data, min_samples, DistanceMetric::Euclidean)
+}
+
+/// `hdbscan_mst` under any distance metric
+fn mutual_reachability_mst(
+    data: &[Vec<f64>],
+    min_samples: usize,
+    metric: DistanceMetric,
+) -> Result<Vec<(usize, usize, f64)>> {
+    let n = data.len();
+    if n == 0 {
+        return Err(anyhow!("Empty input data"));
+    }
+    if min_samples > n {
+        return Err(anyhow!("min_samples ({}) exceeds the number of data points ({})", min_samples, n));
+    }
+    
+    let core = core_distances(data, min_samples, metric);
+    
+    let mut in_tree = vec![false; n];
+    let mut best = vec![f64::INFINITY; n];
+    let mut parent = vec![0; n];
+    let mut edges = Vec::with_capacity(n - 1);
+    let mut current = 0;
+    in_tree[0] = true;
+    
+    for _ in 1..n {
+        for j in 0..n {
+            if in_tree[j] {
+                continue;
+            }
+            let reachability = metric
+                .distance(&data[current], &data[j])
+                .max(core[current])
+                .max(core[j]);
+            if reachability < best[j] {
+                best[j] = reachability;
+                parent[j] = current;
+            }
+        }
+        
+        let next = (0..n)
+            .filter(|&j| !in_tree[j])
+            .fold(None, |acc: Option<usize>, j| match acc {
+                Some(b) if best[b] <= best[j] => Some(b),
+                _ => Some(j),
+            })
+            .expect("at least one point remains outside the tree");
+        
+        edges.push((parent[next], next, best[next]));
+        in_tree[next] = true;
+        current = next;
+    }
+    
+    edges.sort_by(|a, b| a.2.total_cmp(&b.2));
+    Ok(edges)
+}
+
+/// Computes every point's core distance: the distance to its `min_samples`-th nearest
+/// neighbor, counting the point itself (so `min_samples <= 1` gives 0)
+pub(crate) fn core_distances(data: &[Vec<f64>], min_samples: usize, metric: DistanceMetric) -> Vec<f64> {
+    if min_samples <= 1 {
+        return vec![0.0; data.len()];
+    }
+    
+    data.iter()
+        .map(|point| {
+            let mut distances: Vec<f64> = data.iter().map(|other| metric.distance(point, other)).collect();
+            let kth = (min_samples - 1).min(distances.len() - 1);
+            *distances.select_nth_unstable_by(kth, |a, b| a.total_cmp(b)).1
+        })
+        .collect()
+}
+
+/// Assigns new points to an existing HDBSCAN clustering without refitting
+///
+/// Approximates the Python `hdbscan` library's `approximate_predict`: each new point gets
+/// the label of the training point closest to it in mutual reachability distance
+/// `max(core(new), core(train), distance)`, where core distances use `min_samples` and all
+/// distances are measured with the `metric` the clustering was fitted with. The
+/// confidence is the new point's density level relative to the densest point of that cluster,
+/// `min core distance in the cluster / mutual reachability distance`, capped at 1. New points
+/// closest to an outlier are predicted as outliers, with confidence 0. Exact search over the
+/// training data: O(N²) for the training core distances plus O(N) per new point.
+///
+/// # Arguments
+/// * `train_data` - The data points the clustering was computed on
+/// * `result` - The HDBSCAN clustering result
+/// * `new_points` - The points to assign
+/// * `min_samples` - The `min_samples` used for the clustering
+/// * `metric` - The distance metric used for the clustering
+///
+/// # Returns
+/// * `Result<Vec<(Option<usize>, f64)>>` - Per new point, the predicted cluster ID (`None` for
+///   outliers, as in `ClusteringResult::labels`) and a confidence in [0, 1], or error
+pub fn hdbscan_approximate_predict(
+    train_data: &[Vec<f64>],
+    result: &ClusteringResult,
+    new_points: &[Vec<f64>],
+    min_samples: usize,
+    metric: DistanceMetric,
+) -> Result<Vec<(Option<usize>, f64)>> {
+    if train_data.is_empty() {
+        return Err(anyhow!("Empty training data"));
+    }
+    if train_data.len() != result.assignments.len() {
+        return Err(anyhow!(
+            "Training data has {} points but the clustering covers {}",
+            train_data.len(),
+            result.assignments.len()
+        ));
+    }
+    
+    let core = core_distances(train_data, min_samples, metric);
+    let labels = result.labels();
+    let densest: HashMap<usize, f64> = result
+        .clusters
+        .iter()
+        .map(|(&cluster_id, indices)| {
+            (cluster_id, indices.iter().map(|&i| core[i]).fold(f64::INFINITY, f64::min))
+        })
+        .collect();
+    
+    Ok(new_points
+        .iter()
+        .map(|point| {
+            let distances: Vec<f64> = train_data.iter().map(|t| metric.distance(point, t)).collect();
+            // Core distance of the new point, counting itself as its first neighbor
+            let point_core = if min_samples <= 1 {
+                0.0
+            } else {
+                let mut sorted = distances.clone();
+                let kth = (min_samples - 2).min(sorted.len() - 1);
+                *sorted.select_nth_unstable_by(kth, |a, b| a.total_cmp(b)).1
+            };
+            
+            let (nearest, reachability) = distances
+                .iter()
+                .enumerate()
+                .map(|(i, &d)| (i, d.max(core[i]).max(point_core)))
+                .fold((0, f64::INFINITY), |best, (i, r)| if r < best.1 { (i, r) } else { best });
+            
+            match labels[nearest] {
+                Some(cluster_id) => {
+                    let confidence = if reachability > 0.0 {
+                        (densest[&cluster_id] / reachability).min(1.0)
+                    } else {
+                        1.0
+                    };
+                    (Some(cluster_id), confidence)
+                }
+                None => (None, 0.0),
+            }
+        })
+        .collect())
+}
+
+/// Performs DBSCAN clustering on a dataset
+///
+/// Points with at least `min_points` neighbors within `eps` (counting themselves, as in
+/// scikit-learn) are core points; clusters are the connected groups of core points plus the
+/// points within `eps` of them, and everything else is an outlier. Unlike HDBSCAN, the
+/// density threshold is fixed, so results are reproducible against other DBSCAN
+/// implementations. Cluster IDs are contiguous from 1, numbered by each cluster's smallest
+/// point index; outliers get assignment 0.
+///
+/// petal-clustering answers the neighborhood queries with a ball tree, which needs the
+/// triangle inequality, so it is used for Euclidean and Manhattan only. Cosine and DTW
+/// neighborhoods are found by brute force, in O(N²) distance evaluations.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `eps` - Neighborhood radius (see `suggest_dbscan_eps` for a starting value)
+/// * `min_points` - Minimum neighborhood size of a core point
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result or error
+pub fn dbscan_clustering(
+    data: &[Vec<f64>],
+    eps: f64,
+    min_points: usize,
+    metric: DistanceMetric,
+) -> Result<ClusteringResult> {
+    let nrows = data.len();
+    if nrows == 0 {
+        return Err(anyhow!("Empty input data"));
+    }
+    if eps.is_nan() || eps < 0.0 {
+        return Err(anyhow!("eps must be non-negative, got {}", eps));
+    }
+    if min_points == 0 {
+        return Err(anyhow!("min_points must be at least 1"));
+    }
+    
+    let ncols = validate_row_lengths(data)?;
+    
+    let (mut members, mut outliers) = if metric.is_metric() {
+        let flat_data = flatten_rows(data);
+        let data_array = Array2::from_shape_vec((nrows, ncols), flat_data)
+            .map_err(|e| anyhow!("Failed to reshape data: {}", e))?;
+        
+        let mut dbscan = Dbscan {
+            eps,
+            min_samples: min_points,
+            metric: PetalMetric(metric),
+        };
+        let (petal_clusters, outliers) = PetalFit::fit(&mut dbscan, &data_array);
+        (petal_clusters.into_values().collect(), outliers)
+    } else {
+        brute_force_dbscan(data, eps, min_points, metric)
+    };
+    
+    for indices in members.iter_mut() {
+        indices.sort_unstable();
+    }
+    members.sort_by_key(|indices| indices[0]);
+    outliers.sort_unstable();
+    
+    let clusters: HashMap<usize, Vec<usize>> =
+        members.into_iter().enumerate().map(|(idx, indices)| (idx + 1, indices)).collect();
+    Ok(ClusteringResult::from_clusters(clusters, outliers, nrows))
+}
+
+/// DBSCAN with exact eps-neighborhoods, for distances that are not true metrics
+///
+/// Returns the members of every cluster and the outliers, both unsorted. Border points
+/// within `eps` of several clusters join the first one that reaches them.
+fn brute_force_dbscan(
+    data: &[Vec<f64>],
+    eps: f64,
+    min_points: usize,
+    metric: DistanceMetric,
+) -> (Vec<Vec<usize>>, Vec<usize>) {
+    let neighborhoods: Vec<Vec<usize>> = data
+        .iter()
+        .map(|point| (0..data.len()).filter(|&j| metric.distance(point, &data[j]) <= eps).collect())
+        .collect();
+    let is_core: Vec<bool> = neighborhoods.iter().map(|n| n.len() >= min_points).collect();
+    
+    let mut visited = vec![false; data.len()];
+    let mut members = Vec::new();
+    for seed in 0..data.len() {
+        if visited[seed] || !is_core[seed] {
+            continue;
+        }
+        visited[seed] = true;
+        let mut cluster = Vec::new();
+        let mut queue = vec![seed];
+        while let Some(idx) = queue.pop() {
+            cluster.push(idx);
+            if is_core[idx] {
+                for &neighbor in &neighborhoods[idx] {
+                    if !std::mem::replace(&mut visited[neighbor], true) {
+                        queue.push(neighbor);
+                    }
+                }
+            }
+        }
+        members.push(cluster);
+    }
+    
+    let outliers = (0..data.len()).filter(|&idx| !visited[idx]).collect();
+    (members, outliers)
+}
+
+/// Suggests an `eps` value for DBSCAN from the knee of the k-distance curve
+///
+/// Computes the sorted k-distance curve with `k = min_samples` and locates its knee with the
+/// kneedle algorithm: after normalizing both axes to [0, 1], the knee is the point furthest
+/// below the diagonal. This is a heuristic starting point; noisy or multi-density data may
+/// need a manual adjustment.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points
+/// * `min_samples` - The `min_samples` value intended for DBSCAN
+///
+/// # Returns
+/// * `Result<f64>` - The suggested `eps`, or error
+pub fn suggest_dbscan_eps(data: &[Vec<f64>], min_samples: usize) -> Result<f64> {
+    let curve = k_distances(data, min_samples.max(1))?;
+    let (first, last) = (curve[0], curve[curve.len() - 1]);
+    if curve.len() < 3 || last <= first {
+        return Ok(last);
+    }
+    
+    let x_span = (curve.len() - 1) as f64;
+    let knee = curve
+        .iter()
+        .enumerate()
+        .map(|(i, &y)| (i, i as f64 / x_span - (y - first) / (last - first)))
+        .fold((0, f64::NEG_INFINITY), |best, candidate| {
+            if candidate.1 > best.1 {
+                candidate
+            } else {
+                best
+            }
+        })
+        .0;
+    
+    Ok(curve[knee])
+}
+
+/// Optional parameters of GMM (Gaussian Mixture Model) fitting
+///
+/// Override single fields with `GmmParams { seed: Some(7), ..Default::default() }`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GmmParams<'a> {
+    /// Number of runs to perform (default: 10)
+    pub n_runs: Option<usize>,
+    /// Convergence tolerance (default: 1e-4)
+    pub tolerance: Option<f64>,
+    /// Random seed for reproducibility (default: 42)
+    pub seed: Option<u64>,
+    /// Structure of the component covariances (default: `CovarianceType::Full`, the only type
+    /// linfa supports; the others are fitted in-crate)
+    pub covariance_type: Option<CovarianceType>,
+    /// Value added to the diagonal of every fitted covariance (default: 1e-6). This floors the
+    /// variance along every axis, keeping the covariances invertible when a component
+    /// collapses onto a lower-dimensional subspace; larger values give rounder, wider
+    /// components
+    pub reg_covariance: Option<f64>,
+    /// Optional clustering of the data (e.g. from `kmeans_clustering`) to warm-start EM from
+    /// (default: none, initialize internally). Each cluster seeds one component with its
+    /// member mean, covariance and share of the clustered points; outliers are ignored. A warm
+    /// start is deterministic, so a single EM run replaces the `n_runs` restarts. It must have
+    /// exactly `n_clusters` clusters
+    pub init_from: Option<&'a ClusteringResult>,
+}
+
+/// Performs GMM (Gaussian Mixture Model) clustering on a dataset
+///
+/// Uses the defaults of `GmmParams` for everything but the listed parameters; see
+/// `gmm_clustering_with_params` for constrained covariances and warm starts.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to create
+/// * `n_runs` - Number of runs to perform (default: 10)
+/// * `tolerance` - Convergence tolerance (default: 1e-4)
+/// * `seed` - Random seed for reproducibility (default: 42)
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result or error
+pub fn gmm_clustering(
+    data: &[Vec<f64>],
+    n_clusters: usize,
+    n_runs: Option<usize>,
+    tolerance: Option<f64>,
+    seed: Option<u64>,
+) -> Result<ClusteringResult> {
+    let params = GmmParams {
+        n_runs,
+        tolerance,
+        seed,
+        ..Default::default()
+    };
+    gmm_clustering_with_params(data, n_clusters, params)
+}
+
+/// Performs GMM (Gaussian Mixture Model) clustering on a dataset with every optional parameter
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to create
+/// * `params` - The optional parameters (see `GmmParams`)
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result or error
+pub fn gmm_clustering_with_params(
+    data: &[Vec<f64>],
+    n_clusters: usize,
+    params: GmmParams,
+) -> Result<ClusteringResult> {
+    fit_gmm(data, n_clusters, params).map(|gmm| gmm.clustering)
+}
+
+/// Structure of the covariance matrices of a Gaussian Mixture Model
+///
+/// Constrained types have fewer parameters, so they fit faster and are far less prone to
+/// singular covariances on high-dimensional data, at the cost of less flexible cluster shapes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CovarianceType {
+    /// Every component has its own unconstrained covariance matrix
+    #[default]
+    Full,
+    /// All components share one unconstrained covariance matrix
+    Tied,
+    /// Every component has its own diagonal covariance matrix (axis-aligned ellipsoids)
+    Diagonal,
+    /// Every component has its own single variance (spheres)
+    Spherical,
+}
+
+/// Result of fitting a Gaussian Mixture Model, including the fitted components
+#[derive(Debug, Clone)]
+pub struct GmmResult {
+    /// The hard cluster assignments (cluster ID = component index)
+    pub clustering: ClusteringResult,
+    /// Mean of each component, indexed by component
+    pub means: Vec<Vec<f64>>,
+    /// Mixing weight of each component, indexed by component
+    pub weights: Vec<f64>,
+    /// Covariance matrix of each component, indexed by component
+    pub covariances: Vec<Array2<f64>>,
+    /// Posterior probability of each component for each fitted point (rows sum to 1); the
+    /// hard assignment is the most probable component
+    pub probabilities: Vec<Vec<f64>>,
+}
+
+impl GmmResult {
+    /// Computes the posterior component probabilities of new points under the fitted model
+    ///
+    /// # Arguments
+    /// * `points` - The points to evaluate, with the dimensionality of the fitted data
+    ///
+    /// # Returns
+    /// * `Result<Vec<Vec<f64>>>` - Per point, the probability of each component, or error if a
+    ///   point has the wrong dimensionality or a covariance is not positive definite
+    pub fn predict_proba(&self, points: &[Vec<f64>]) -> Result<Vec<Vec<f64>>> {
+        let n_dims = self.means.first().map_or(0, |mean| mean.len());
+        if let Some(point) = points.iter().find(|point| point.len() != n_dims) {
+            return Err(anyhow!("Point has {} dimensions but the model has {}", point.len(), n_dims));
+        }
+        let (responsibilities, _) = gmm_e_step(points, &self.means, &self.covariances, &self.weights)?;
+        Ok(responsibilities.outer_iter().map(|row| row.to_vec()).collect())
+    }
+    
+    /// Finds the fitted points whose most probable component is not a confident choice
+    ///
+    /// # Arguments
+    /// * `threshold` - Points whose highest component probability is below this are ambiguous
+    ///
+    /// # Returns
+    /// * `Vec<usize>` - Indices of the ambiguous points, ascending
+    pub fn ambiguous_points(&self, threshold: f64) -> Vec<usize> {
+        self.probabilities
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.iter().cloned().fold(0.0, f64::max) < threshold)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+}
+
+/// Fits a GMM (Gaussian Mixture Model) to a dataset and keeps the fitted components
+///
+/// Takes the same arguments as `gmm_clustering_with_params`, which only returns the
+/// assignments. Full covariances are fitted with linfa. linfa only implements full
+/// covariances, so the other types run the in-crate EM loop `n_runs` times, each initialized
+/// from `kmeans_with_trajectory` with a different seed, and keep the run with the highest
+/// log-likelihood. Covariances are always returned as full matrices.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to create
+/// * `params` - The optional parameters (see `GmmParams`)
+///
+/// # Returns
+/// * `Result<GmmResult>` - The clustering result with the component parameters, or error
+pub fn fit_gmm(data: &[Vec<f64>], n_clusters: usize, params: GmmParams) -> Result<GmmResult> {
+    let rng = Xoshiro256Plus::seed_from_u64(params.seed.unwrap_or(42));
+    fit_gmm_with_rng(data, n_clusters, params, rng)
+}
+
+/// Same as `fit_gmm`, with a caller-supplied random number generator instead of a seed
+///
+/// Lets a specific PRNG be used, e.g. to reproduce the initialization of another pipeline.
+/// `fit_gmm` is this function with `Xoshiro256Plus` seeded from `params.seed`.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to create
+/// * `params` - The optional parameters (see `GmmParams`); `seed` is ignored, and with
+///   `init_from` the random number generator is unused
+/// * `rng` - Random number generator driving the initialization
+///
+/// # Returns
+/// * `Result<GmmResult>` - The clustering result with the component parameters, or error
+pub fn fit_gmm_with_rng<R: Rng + Clone>(
+    data: &[Vec<f64>],
+    n_clusters: usize,
+    params: GmmParams,
+    mut rng: R,
+) -> Result<GmmResult> {
+    let GmmParams {
+        n_runs,
+        tolerance,
+        covariance_type,
+        reg_covariance,
+        init_from,
+        ..
+    } = params;
+    
+    // Check for empty data
+    let nrows = data.len();
+    if nrows == 0 {
+        return Err(anyhow!("Empty input data"));
+    }
+    let ncols = validate_row_lengths(data)?;
+    
+    let reg_covariance = reg_covariance.unwrap_or(GMM_REG_COVARIANCE);
+    if reg_covariance.is_nan() || reg_covariance < 0.0 {
+        return Err(anyhow!("reg_covariance must be non-negative, got {}", reg_covariance));
+    }
+    
+    let covariance_type = covariance_type.unwrap_or_default();
+    if let Some(init) = init_from {
+        let (means, covariances, weights) =
+            gmm_m_step_from_clustering(data, init, n_clusters, reg_covariance, covariance_type)?;
+        let fit = gmm_em(
+            data,
+            means,
+            covariances,
+            weights,
+            100,
+            tolerance.unwrap_or(1e-4),
+            reg_covariance,
+            covariance_type,
+        )?;
+        return Ok(fit.to_result());
+    }
+    
+    if covariance_type != CovarianceType::Full {
+        let mut best: Option<GmmEmFit> = None;
+        for _ in 0..n_runs.unwrap_or(10).max(1) {
+            let (init, _) = kmeans_with_trajectory(data, n_clusters, None, None, Some(rng.gen()))?;
+            let (means, covariances, weights) =
+                gmm_m_step_hard(data, &init.assignments, n_clusters, reg_covariance, covariance_type);
+            let fit = gmm_em(
+                data,
+                means,
+                covariances,
+                weights,
+                100,
+                tolerance.unwrap_or(1e-4),
+                reg_covariance,
+                covariance_type,
+            )?;
+            if !matches!(&best, Some(b) if fit.final_log_likelihood() <= b.final_log_likelihood()) {
+                best = Some(fit);
+            }
+        }
+        return Ok(best.expect("at least one run").to_result());
+    }
+    
+    // Convert data to ndarray format for linfa
+    let flat_data = flatten_rows(data);
+    
+    let data_array = Array2::from_shape_vec((nrows, ncols), flat_data)
+        .map_err(|e| anyhow!("Failed to reshape data: {}", e))?;
+    
+    // Create dataset for GMM
+    let dataset = DatasetBase::from(data_array);
+    
+    // Configure and run GMM
+    let gmm = GaussianMixtureModel::params(n_clusters)
+        .n_runs(n_runs.unwrap_or(10) as u64)
+        .tolerance(tolerance.unwrap_or(1e-4))
+        .reg_covariance(reg_covariance)
+        .with_rng(rng)
+        .fit(&dataset)
+        .map_err(|e| {
+            anyhow!(
+                "GMM fitting failed with reg_covariance = {}: {}. Singular covariances can usually be \
+                 avoided with a larger reg_covariance or a constrained CovarianceType",
+                reg_covariance,
+                e
+            )
+        })?;
+    
+    // Get cluster assignments
+    let clustered_data = gmm.predict(dataset);
+    let targets = clustered_data.targets();
+    
+    // Convert to the ClusteringResult format
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut assignments = vec![0; nrows];
+    
+    for (idx, &cluster_id) in targets.iter().enumerate() {
+        // Store assignment
+        let cluster_id_usize = cluster_id as usize;
+        assignments[idx] = cluster_id_usize;
+        
+        // Add to clusters map
+        clusters.entry(cluster_id_usize)
+            .or_insert_with(Vec::new)
+            .push(idx);
+    }
+    
+    // GMM assigns all points to clusters, so there are no outliers
+    let outliers = Vec::new();
+    
+    let means: Vec<Vec<f64>> = gmm.means().outer_iter().map(|row| row.to_vec()).collect();
+    let weights = gmm.weights().to_vec();
+    let covariances: Vec<Array2<f64>> = gmm.covariances().outer_iter().map(|cov| cov.to_owned()).collect();
+    let (responsibilities, _) = gmm_e_step(data, &means, &covariances, &weights)?;
+    
+    Ok(GmmResult {
+        clustering: ClusteringResult {
+            clusters,
+            outliers,
+            assignments,
+            degenerate_centroids: Vec::new(),
+            centroids: Some(means.clone()),
+        },
+        means,
+        weights,
+        covariances,
+        probabilities: responsibilities.outer_iter().map(|row| row.to_vec()).collect(),
+    })
+}
+
+/// Finds, for each GMM component, the data point closest to the component mean
+///
+/// Gives a real representative per component instead of the synthetic mean.
+///
+/// # Arguments
+/// * `data` - The data points the GMM was fitted on
+/// * `result` - The fitted GMM
+///
+/// # Returns
+/// * `HashMap<usize, usize>` - Mapping of component IDs to the index of their exemplar point
+pub fn gmm_exemplars(data: &[Vec<f64>], result: &GmmResult) -> HashMap<usize, usize> {
+    result
+        .means
+        .iter()
+        .enumerate()
+        .filter_map(|(component, mean)| {
+            data.iter()
+                .enumerate()
+                .map(|(idx, point)| (idx, euclidean_distance(point, mean)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(idx, _)| (component, idx))
+        })
+        .collect()
+}
+
+/// Fits a GMM and records the log-likelihood after every EM iteration
+///
+/// linfa's GMM exposes no per-iteration hooks, so this runs its own EM loop with full
+/// covariances (a regularization of 1e-6 is added to their diagonals), initialized from
+/// `kmeans_with_trajectory` with the same seed. Results are close to, but not bit-identical
+/// with, `fit_gmm`. A trajectory that is still rising at the last entry means EM stopped early.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to create
+/// * `max_iterations` - Maximum number of EM iterations (default: 100)
+/// * `tolerance` - Stop when the mean log-likelihood improves by less than this (default: 1e-4)
+/// * `seed` - Random seed for the KMeans initialization (default: 42)
+///
+/// # Returns
+/// * `Result<(GmmResult, Vec<f64>)>` - The fitted GMM and the mean per-point log-likelihood at
+///   each iteration, or error
+pub fn gmm_with_trajectory(
+    data: &[Vec<f64>],
+    n_clusters: usize,
+    max_iterations: Option<usize>,
+    tolerance: Option<f64>,
+    seed: Option<u64>,
+) -> Result<(GmmResult, Vec<f64>)> {
+    let (init, _) = kmeans_with_trajectory(data, n_clusters, None, None, seed)?;
+    let (means, covariances, weights) =
+        gmm_m_step_hard(data, &init.assignments, n_clusters, GMM_REG_COVARIANCE, CovarianceType::Full);
+    let fit = gmm_em(
+        data,
+        means,
+        covariances,
+        weights,
+        max_iterations.unwrap_or(100),
+        tolerance.unwrap_or(1e-4),
+        GMM_REG_COVARIANCE,
+        CovarianceType::Full,
+    )?;
+    
+    Ok((fit.to_result(), fit.log_likelihoods))
+}
+
+/// Default regularization added to the diagonal of fitted GMM covariances
+const GMM_REG_COVARIANCE: f64 = 1e-6;
+
+/// Parameters and diagnostics of a GMM fitted by `gmm_em`
+struct GmmEmFit {
+    means: Vec<Vec<f64>>,
+    covariances: Vec<Array2<f64>>,
+    weights: Vec<f64>,
+    /// Posterior probability of every component for every point (N × K)
+    responsibilities: Array2<f64>,
+    /// Mean per-point log-likelihood after each iteration
+    log_likelihoods: Vec<f64>,
+}
+
+impl GmmEmFit {
+    fn final_log_likelihood(&self) -> f64 {
+        self.log_likelihoods.last().cloned().unwrap_or(f64::NEG_INFINITY)
+    }
+    
+    fn to_result(&self) -> GmmResult {
+        // Hard assignment to the most probable component, lowest index on ties
+        let assignments: Vec<usize> = self
+            .responsibilities
+            .outer_iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .fold((0, f64::NEG_INFINITY), |best, (c, &p)| if p > best.1 { (c, p) } else { best })
+                    .0
+            })
+            .collect();
+        let indices: Vec<usize> = (0..assignments.len()).collect();
+        
+        GmmResult {
+            clustering: ClusteringResult {
+                clusters: group_by_cluster(&assignments, &indices),
+                outliers: Vec::new(),
+                assignments,
+                degenerate_centroids: Vec::new(),
+                centroids: Some(self.means.clone()),
+            },
+            means: self.means.clone(),
+            weights: self.weights.clone(),
+            covariances: self.covariances.clone(),
+            probabilities: self.responsibilities.outer_iter().map(|row| row.to_vec()).collect(),
+        }
+    }
+}
+
+/// Runs EM from the given parameters until the mean log-likelihood stops improving
+#[allow(clippy::too_many_arguments)]
+fn gmm_em(
+    data: &[Vec<f64>],
+    mut means: Vec<Vec<f64>>,
+    mut covariances: Vec<Array2<f64>>,
+    mut weights: Vec<f64>,
+    max_iterations: usize,
+    tolerance: f64,
+    reg_covariance: f64,
+    covariance_type: CovarianceType,
+) -> Result<GmmEmFit> {
+    let mut log_likelihoods = Vec::new();
+    loop {
+        let (responsibilities, log_likelihood) = gmm_e_step(data, &means, &covariances, &weights)?;
+        let converged = log_likelihoods
+            .last()
+            .is_some_and(|&previous: &f64| (log_likelihood - previous).abs() < tolerance);
+        log_likelihoods.push(log_likelihood);
+        
+        if converged || log_likelihoods.len() > max_iterations {
+            return Ok(GmmEmFit {
+                means,
+                covariances,
+                weights,
+                responsibilities,
+                log_likelihoods,
+            });
+        }
+        (means, covariances, weights) = gmm_m_step(data, &responsibilities, reg_covariance, covariance_type);
+    }
+}
+
+/// Computes the component responsibilities and the mean per-point log-likelihood
+fn gmm_e_step(
+    data: &[Vec<f64>],
+    means: &[Vec<f64>],
+    covariances: &[Array2<f64>],
+    weights: &[f64],
+) -> Result<(Array2<f64>, f64)> {
+    let choleskys = covariances
+        .iter()
+        .enumerate()
+        .map(|(c, cov)| {
+            cholesky(cov).ok_or_else(|| {
+                anyhow!("Covariance of component {} is not positive definite; increase reg_covariance", c)
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    
+    let mut responsibilities = Array2::zeros((data.len(), means.len()));
+    let mut total_log_likelihood = 0.0;
+    for (i, point) in data.iter().enumerate() {
+        let log_probs: Vec<f64> = (0..means.len())
+            .map(|c| weights[c].ln() + gaussian_log_density(point, &means[c], &choleskys[c]))
+            .collect();
+        let max = log_probs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let log_norm = max + log_probs.iter().map(|&lp| (lp - max).exp()).sum::<f64>().ln();
+        
+        for (c, &lp) in log_probs.iter().enumerate() {
+            responsibilities[[i, c]] = (lp - log_norm).exp();
+        }
+        total_log_likelihood += log_norm;
+    }
+    
+    Ok((responsibilities, total_log_likelihood / data.len() as f64))
+}
+
+/// Means, covariances and weights of the components of a GMM, indexed by component
+type GmmComponents = (Vec<Vec<f64>>, Vec<Array2<f64>>, Vec<f64>);
+
+/// Re-estimates means, covariances (as full matrices) and weights from soft responsibilities
+fn gmm_m_step(
+    data: &[Vec<f64>],
+    responsibilities: &Array2<f64>,
+    reg_covariance: f64,
+    covariance_type: CovarianceType,
+) -> GmmComponents {
+    let n_dims = data[0].len();
+    let n_components = responsibilities.ncols();
+    let mut means = Vec::with_capacity(n_components);
+    let mut covariances = Vec::with_capacity(n_components);
+    let mut weights = Vec::with_capacity(n_components);
+    
+    for c in 0..n_components {
+        let resp = responsibilities.column(c);
+        // Keeps empty components finite, as scikit-learn does
+        let total = resp.sum() + 10.0 * f64::EPSILON;
+        
+        let mut mean = vec![0.0; n_dims];
+        for (point, &r) in data.iter().zip(resp.iter()) {
+            for (m, &x) in mean.iter_mut().zip(point.iter()) {
+                *m += r * x;
+            }
+        }
+        mean.iter_mut().for_each(|m| *m /= total);
+        
+        let mut cov = Array2::<f64>::zeros((n_dims, n_dims));
+        for (point, &r) in data.iter().zip(resp.iter()) {
+            let diff: Vec<f64> = point.iter().zip(mean.iter()).map(|(x, m)| x - m).collect();
+            for a in 0..n_dims {
+                for b in 0..=a {
+                    cov[[a, b]] += r * diff[a] * diff[b];
+                }
+            }
+        }
+        for a in 0..n_dims {
+            for b in 0..a {
+                cov[[a, b]] /= total;
+                cov[[b, a]] = cov[[a, b]];
+            }
+            cov[[a, a]] /= total;
+        }
+        
+        match covariance_type {
+            CovarianceType::Full | CovarianceType::Tied => {}
+            CovarianceType::Diagonal => {
+                cov = Array2::from_diag(&cov.diag());
+            }
+            CovarianceType::Spherical => {
+                let variance = cov.diag().mean().unwrap_or(0.0);
+                cov = Array2::eye(n_dims) * variance;
+            }
+        }
+        
+        means.push(mean);
+        covariances.push(cov);
+        weights.push(total / data.len() as f64);
+    }
+    
+    // The shared covariance is the weighted average of the per-component scatter matrices
+    if covariance_type == CovarianceType::Tied {
+        let mut tied = Array2::<f64>::zeros((n_dims, n_dims));
+        for (cov, &weight) in covariances.iter().zip(weights.iter()) {
+            tied.scaled_add(weight, cov);
+        }
+        covariances = vec![tied; n_components];
+    }
+    for cov in covariances.iter_mut() {
+        for a in 0..n_dims {
+            cov[[a, a]] += reg_covariance;
+        }
+    }
+    
+    (means, covariances, weights)
+}
+
+/// M-step from hard assignments, used to initialize EM from a clustering
+fn gmm_m_step_hard(
+    data: &[Vec<f64>],
+    assignments: &[usize],
+    n_components: usize,
+    reg_covariance: f64,
+    covariance_type: CovarianceType,
+) -> GmmComponents {
+    let mut responsibilities = Array2::zeros((data.len(), n_components));
+    for (i, &cluster) in assignments.iter().enumerate() {
+        responsibilities[[i, cluster]] = 1.0;
+    }
+    gmm_m_step(data, &responsibilities, reg_covariance, covariance_type)
+}
+
+/// M-step from a caller-supplied clustering, with one component per cluster in ascending ID
+/// order; outliers get no responsibility
+fn gmm_m_step_from_clustering(
+    data: &[Vec<f64>],
+    init: &ClusteringResult,
+    n_components: usize,
+    reg_covariance: f64,
+    covariance_type: CovarianceType,
+) -> Result<GmmComponents> {
+    if init.clusters.len() != n_components {
+        return Err(anyhow!(
+            "init_from has {} clusters but n_clusters is {}",
+            init.clusters.len(),
+            n_components
+        ));
+    }
+    if init.assignments.len() != data.len() {
+        return Err(anyhow!(
+            "init_from covers {} points but data has {}",
+            init.assignments.len(),
+            data.len()
+        ));
+    }
+    
+    let mut responsibilities = Array2::zeros((data.len(), n_components));
+    for (component, (_, members)) in init.clusters_sorted().into_iter().enumerate() {
+        for idx in members {
+            responsibilities[[idx, component]] = 1.0;
+        }
+    }
+    let (means, covariances, mut weights) =
+        gmm_m_step(data, &responsibilities, reg_covariance, covariance_type);
+    // Outliers leave the weights summing to the clustered fraction
+    let total: f64 = weights.iter().sum();
+    weights.iter_mut().for_each(|w| *w /= total);
+    Ok((means, covariances, weights))
+}
+
+/// Lower-triangular Cholesky factor of a symmetric matrix, or `None` if it is not positive definite
+fn cholesky(matrix: &Array2<f64>) -> Option<Array2<f64>> {
+    let n = matrix.nrows();
+    let mut lower = Array2::<f64>::zeros((n, n));
+    for j in 0..n {
+        let diag = matrix[[j, j]] - (0..j).map(|k| lower[[j, k]].powi(2)).sum::<f64>();
+        if diag.is_nan() || diag <= 0.0 {
+            return None;
+        }
+        lower[[j, j]] = diag.sqrt();
+        for i in (j + 1)..n {
+            let dot: f64 = (0..j).map(|k| lower[[i, k]] * lower[[j, k]]).sum();
+            lower[[i, j]] = (matrix[[i, j]] - dot) / lower[[j, j]];
+        }
+    }
+    Some(lower)
+}
+
+/// Log-density of a multivariate normal given the Cholesky factor of its covariance
+fn gaussian_log_density(point: &[f64], mean: &[f64], cholesky: &Array2<f64>) -> f64 {
+    let n = mean.len();
+    // Solve L z = (x - mean) by forward substitution; |z|² is the Mahalanobis distance
+    let mut z = vec![0.0; n];
+    for i in 0..n {
+        let dot: f64 = (0..i).map(|k| cholesky[[i, k]] * z[k]).sum();
+        z[i] = (point[i] - mean[i] - dot) / cholesky[[i, i]];
+    }
+    let mahalanobis: f64 = z.iter().map(|v| v * v).sum();
+    let log_det: f64 = 2.0 * (0..n).map(|i| cholesky[[i, i]].ln()).sum::<f64>();
+    
+    -0.5 * (n as f64 * (2.0 * std::f64::consts::PI).ln() + log_det + mahalanobis)
+}
+
+/// Performs K-means clustering on a dataset
+///
+/// One-shot form of `fit_kmeans`, for when the model is not needed afterwards.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to create
+/// * `max_iterations` - Maximum number of iterations (default: 100)
+/// * `tolerance` - Convergence tolerance (default: 1e-4)
+/// * `seed` - Random seed for reproducibility (default: 42)
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result or error
+pub fn kmeans_clustering(
+    data: &[Vec<f64>],
+    n_clusters: usize,
+    max_iterations: Option<usize>,
+    tolerance: Option<f64>,
+    seed: Option<u64>,
+) -> Result<ClusteringResult> {
+    fit_kmeans(data, n_clusters, max_iterations, tolerance, seed).map(|model| model.clustering)
+}
+
+/// Same as `kmeans_clustering`, with a caller-supplied random number generator instead of a seed
+///
+/// Lets a specific PRNG be used, e.g. to reproduce the initialization of another pipeline.
+/// `kmeans_clustering` is this function with `Xoshiro256Plus` seeded from `seed`.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to create
+/// * `max_iterations` - Maximum number of iterations (default: 100)
+/// * `tolerance` - Convergence tolerance (default: 1e-4)
+/// * `rng` - Random number generator driving the initialization
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result or error
+pub fn kmeans_clustering_with_rng<R: Rng + Clone>(
+    data: &[Vec<f64>],
+    n_clusters: usize,
+    max_iterations: Option<usize>,
+    tolerance: Option<f64>,
+    rng: R,
+) -> Result<ClusteringResult> {
+    fit_kmeans_with_rng(data, n_clusters, max_iterations, tolerance, rng).map(|model| model.clustering)
+}
+
+/// A fitted KMeans model that can assign new points without refitting
+#[derive(Debug, Clone)]
+pub struct KMeansModel {
+    /// Cluster centroids, indexed by cluster ID
+    pub centroids: Vec<Vec<f64>>,
+    /// The clustering of the data the model was fitted on
+    pub clustering: ClusteringResult,
+}
+
+impl KMeansModel {
+    /// Assigns points to the cluster of their nearest centroid (Euclidean)
+    ///
+    /// Ties go to the smallest cluster ID, as in `assign_to_nearest_centroid`.
+    ///
+    /// # Arguments
+    /// * `points` - The points to assign, with the dimensionality of the fitted data
+    ///
+    /// # Returns
+    /// * `Result<Vec<usize>>` - Cluster ID per point, or error if a point's dimensionality
+    ///   differs from the centroids'
+    pub fn predict(&self, points: &[Vec<f64>]) -> Result<Vec<usize>> {
+        let dim = self.centroids.first().map_or(0, Vec::len);
+        if let Some((i, point)) = points.iter().enumerate().find(|(_, point)| point.len() != dim) {
+            return Err(anyhow!(
+                "Point {} has {} dimensions, but the model was fitted on {}",
+                i,
+                point.len(),
+                dim
+            ));
+        }
+        assign_to_nearest_centroid(points, &self.centroids, DistanceMetric::Euclidean)
+    }
+}
+
+/// Fits a KMeans model to a dataset and keeps its centroids for later predictions
+///
+/// Train once with this, then stream new points through `KMeansModel::predict`.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to create
+/// * `max_iterations` - Maximum number of iterations (default: 100)
+/// * `tolerance` - Convergence tolerance (default: 1e-4)
+/// * `seed` - Random seed for reproducibility (default: 42)
+///
+/// # Returns
+/// * `Result<KMeansModel>` - The fitted model, or error
+pub fn fit_kmeans(
+    data: &[Vec<f64>],
+    n_clusters: usize,
+    max_iterations: Option<usize>,
+    tolerance: Option<f64>,
+    seed: Option<u64>,
+) -> Result<KMeansModel> {
+    fit_kmeans_with_rng(
+        data,
+        n_clusters,
+        max_iterations,
+        tolerance,
+        Xoshiro256Plus::seed_from_u64(seed.unwrap_or(42)),
+    )
+}
+
+/// Same as `fit_kmeans`, with a caller-supplied random number generator instead of a seed
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to create
+/// * `max_iterations` - Maximum number of iterations (default: 100)
+/// * `tolerance` - Convergence tolerance (default: 1e-4)
+/// * `rng` - Random number generator driving the initialization
+///
+/// # Returns
+/// * `Result<KMeansModel>` - The fitted model, or error
+pub fn fit_kmeans_with_rng<R: Rng + Clone>(
+    data: &[Vec<f64>],
+    n_clusters: usize,
+    max_iterations: Option<usize>,
+    tolerance: Option<f64>,
+    rng: R,
+) -> Result<KMeansModel> {
+    // Check for empty data
+    let nrows = data.len();
+    if nrows == 0 {
+        return Err(anyhow!("Empty input data"));
+    }
+    
+    // Convert data to ndarray format for linfa
+    let ncols = validate_row_lengths(data)?;
+    let flat_data = flatten_rows(data);
+    
+    let data_array = Array2::from_shape_vec((nrows, ncols), flat_data)
+        .map_err(|e| anyhow!("Failed to reshape data: {}", e))?;
+    
+    kmeans_on_array(data_array.view(), n_clusters, max_iterations, tolerance, rng)
+}
+
+/// Same as `kmeans_clustering`, on a flat row-major buffer instead of one `Vec` per point
+///
+/// Clusters the buffer in place, without collecting it into a new array first.
+///
+/// # Arguments
+/// * `data` - The data points, row after row
+/// * `n_rows` - Number of data points
+/// * `n_cols` - Number of dimensions per data point
+/// * `n_clusters` - Number of clusters to create
+/// * `max_iterations` - Maximum number of iterations (default: 100)
+/// * `tolerance` - Convergence tolerance (default: 1e-4)
+/// * `seed` - Random seed for reproducibility (default: 42)
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result, or error if the buffer length is not
+///   `n_rows * n_cols`
+pub fn kmeans_clustering_flat(
+    data: &[f64],
+    n_rows: usize,
+    n_cols: usize,
+    n_clusters: usize,
+    max_iterations: Option<usize>,
+    tolerance: Option<f64>,
+    seed: Option<u64>,
+) -> Result<ClusteringResult> {
+    kmeans_on_array(
+        flat_view(data, n_rows, n_cols)?,
+        n_clusters,
+        max_iterations,
+        tolerance,
+        Xoshiro256Plus::seed_from_u64(seed.unwrap_or(42)),
+    )
+    .map(|model| model.clustering)
+}
+
+/// Same as `kmeans_clustering`, on single-precision data
+///
+/// linfa runs KMeans in the input precision, so the data is never widened to f64 and takes
+/// half the memory. Lloyd iterations are well conditioned in f32; only a `tolerance` below
+/// f32 resolution (about 1e-7 relative to the data scale) makes no sense here. There is no
+/// f32 variant of `gmm_clustering` on purpose: EM sums log-likelihoods and inverts
+/// covariances, which in f32 can stall convergence or fail on near-singular components.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to create
+/// * `max_iterations` - Maximum number of iterations (default: 100)
+/// * `tolerance` - Convergence tolerance (default: 1e-4)
+/// * `seed` - Random seed for reproducibility (default: 42)
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result (centroids widened to f64) or error
+pub fn kmeans_clustering_f32(
+    data: &[Vec<f32>],
+    n_clusters: usize,
+    max_iterations: Option<usize>,
+    tolerance: Option<f64>,
+    seed: Option<u64>,
+) -> Result<ClusteringResult> {
+    let nrows = data.len();
+    if nrows == 0 {
+        return Err(anyhow!("Empty input data"));
+    }
+    
+    let ncols = validate_row_lengths(data)?;
+    let flat_data = flatten_rows(data);
+    
+    let data_array = Array2::from_shape_vec((nrows, ncols), flat_data)
+        .map_err(|e| anyhow!("Failed to reshape data: {}", e))?;
+    
+    kmeans_on_array(
+        data_array.view(),
+        n_clusters,
+        max_iterations,
+        tolerance,
+        Xoshiro256Plus::seed_from_u64(seed.unwrap_or(42)),
+    )
+    .map(|model| model.clustering)
+}
+
+/// KMeans on a non-empty 2D array, shared by the nested, flat and f32 entry points
+fn kmeans_on_array<F: linfa::Float, R: Rng + Clone>(
+    data_array: ArrayView2<F>,
+    n_clusters: usize,
+    max_iterations: Option<usize>,
+    tolerance: Option<f64>,
+    rng: R,
+) -> Result<KMeansModel> {
+    let nrows = data_array.nrows();
+    
+    // Create dataset for KMeans
+    let dataset = DatasetBase::from(data_array);
+    
+    // Configure and run KMeans
+    let kmeans = KMeans::params_with_rng(n_clusters, rng)
+        .max_n_iterations(max_iterations.unwrap_or(100) as u64)
+        .tolerance(F::from(tolerance.unwrap_or(1e-4)).expect("f64 converts to any float type"))
+        .fit(&dataset)
+        .map_err(|e| anyhow!("KMeans fitting failed: {}", e))?;
+    
+    // Get cluster assignments
+    let targets = kmeans.predict(&data_array);
+    
+    // Convert to the ClusteringResult format
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut assignments = vec![0; nrows];
+    
+    for (idx, &cluster_id) in targets.iter().enumerate() {
+        // Store assignment
+        let cluster_id_usize = cluster_id as usize;
+        assignments[idx] = cluster_id_usize;
+        
+        // Add to clusters map
+        clusters.entry(cluster_id_usize)
+            .or_insert_with(Vec::new)
+            .push(idx);
+    }
+    
+    // KMeans assigns all points to clusters, so there are no outliers
+    let outliers = Vec::new();
+    
+    // Flag centroid pairs that converged to (nearly) the same point
+    let centroids: Vec<Vec<f64>> = kmeans
+        .centroids()
+        .outer_iter()
+        .map(|row| row.iter().map(|&x| x.to_f64().expect("floats convert to f64")).collect())
+        .collect();
+    let degenerate_centroids = find_degenerate_centroids(&centroids);
+    
+    Ok(KMeansModel {
+        clustering: ClusteringResult {
+            clusters,
+            outliers,
+            assignments,
+            degenerate_centroids,
+            centroids: Some(centroids.clone()),
+        },
+        centroids,
+    })
+}
+
+/// Picks initial cluster seeds with k-means++
+///
+/// The first seed is a uniformly random point; every further seed is drawn with probability
+/// proportional to its squared distance to the nearest seed so far. If no weight is left
+/// (every point coincides with a seed) or the weights are not finite, the first point that is
+/// not yet a seed is taken.
+///
+/// # Arguments
+/// * `n_points` - Number of data points
+/// * `n_clusters` - Number of seeds to pick (at most `n_points`)
+/// * `squared_distance` - Squared distance between the points with the given indices
+/// * `rng` - Random number generator
+///
+/// # Returns
+/// * `Vec<usize>` - Indices of the seed points, in order of selection
+fn kmeans_plus_plus_init<R: Rng>(
+    n_points: usize,
+    n_clusters: usize,
+    squared_distance: impl Fn(usize, usize) -> f64,
+    rng: &mut R,
+) -> Vec<usize> {
+    let mut seeds = vec![rng.gen_range(0..n_points)];
+    let mut closest = vec![f64::INFINITY; n_points];
+    while seeds.len() < n_clusters {
+        let last = *seeds.last().expect("at least one seed");
+        for (point, best) in closest.iter_mut().enumerate() {
+            *best = best.min(squared_distance(point, last));
+        }
+        
+        let total: f64 = closest.iter().sum();
+        let next = if total > 0.0 && total.is_finite() {
+            let mut target = rng.gen::<f64>() * total;
+            closest
+                .iter()
+                .position(|&d| {
+                    target -= d;
+                    target <= 0.0
+                })
+                .unwrap_or(n_points - 1)
+        } else {
+            (0..n_points).find(|i| !seeds.contains(i)).expect("n_clusters <= n_points")
+        };
+        seeds.push(next);
+    }
+    seeds
+}
+
+/// Runs Lloyd iterations from the given centroids
+///
+/// Assigns every point to its nearest centroid (the lowest index on ties, as in
+/// `assign_to_nearest_centroid`), then alternates between replacing the centroid of every
+/// non-empty cluster with `update` and reassigning the points. Empty clusters keep their
+/// previous centroid. Stops after `max_iterations` updates, once the total centroid shift is
+/// at most `tolerance`, or once the assignments no longer change. Every update is followed
+/// by a reassignment, so the returned assignments always match the final centroids.
+///
+/// # Arguments
+/// * `n_points` - Number of data points
+/// * `centroids` - Initial centroids, updated in place
+/// * `max_iterations` - Maximum number of centroid updates
+/// * `tolerance` - Total centroid shift at or below which to stop
+/// * `distance` - Distance from the point with the given index to a centroid; only the order
+///   of distances matters for the assignment
+/// * `update` - New centroid of a cluster from its member indices and previous centroid,
+///   together with the shift between the two
+///
+/// # Returns
+/// * `(Vec<usize>, Vec<f64>)` - The final assignments and, after every assignment step, the
+///   sum of the distances of all points to their assigned centroid
+fn lloyd_iterations<C>(
+    n_points: usize,
+    centroids: &mut [C],
+    max_iterations: usize,
+    tolerance: f64,
+    distance: impl Fn(usize, &C) -> f64,
+    update: impl Fn(&[usize], &C) -> (C, f64),
+) -> (Vec<usize>, Vec<f64>) {
+    let assign = |centroids: &[C]| -> (Vec<usize>, f64) {
+        let mut total = 0.0;
+        let assignments = (0..n_points)
+            .map(|point| {
+                // Strict comparison keeps the lowest index among equidistant centroids
+                let mut best = (0, f64::INFINITY);
+                for (cluster, centroid) in centroids.iter().enumerate() {
+                    let d = distance(point, centroid);
+                    if d < best.1 {
+                        best = (cluster, d);
+                    }
+                }
+                total += best.1;
+                best.0
+            })
+            .collect();
+        (assignments, total)
+    };
+    
+    let indices: Vec<usize> = (0..n_points).collect();
+    let (mut assignments, total) = assign(centroids);
+    let mut trajectory = vec![total];
+    for _ in 0..max_iterations {
+        let mut shift = 0.0;
+        for (cluster, members) in group_by_cluster(&assignments, &indices) {
+            let (new_centroid, moved) = update(&members, &centroids[cluster]);
+            centroids[cluster] = new_centroid;
+            shift += moved;
+        }
+        
+        let (new_assignments, total) = assign(centroids);
+        trajectory.push(total);
+        let converged = shift <= tolerance || new_assignments == assignments;
+        assignments = new_assignments;
+        if converged {
+            break;
+        }
+    }
+    
+    (assignments, trajectory)
+}
+
+/// Performs K-means clustering and records the inertia after every iteration
+///
+/// linfa's KMeans exposes no per-iteration hooks, so this runs its own Lloyd loop with
+/// k-means++ initialization. It is a single run, so results can differ from
+/// `kmeans_clustering`. Inertia never increases between iterations; a trajectory that is
+/// still dropping at the last entry means `max_iterations` was too small.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to create
+/// * `max_iterations` - Maximum number of iterations (default: 100)
+/// * `tolerance` - Stop when the total centroid shift is below this (default: 1e-4)
+/// * `seed` - Random seed for reproducibility (default: 42)
+///
+/// # Returns
+/// * `Result<(ClusteringResult, Vec<f64>)>` - The clustering result and the inertia (sum of
+///   squared distances to the assigned centroid) after each assignment step, or error
+pub fn kmeans_with_trajectory(
+    data: &[Vec<f64>],
+    n_clusters: usize,
+    max_iterations: Option<usize>,
+    tolerance: Option<f64>,
+    seed: Option<u64>,
+) -> Result<(ClusteringResult, Vec<f64>)> {
+    let nrows = data.len();
+    if nrows == 0 {
+        return Err(anyhow!("Empty input data"));
+    }
+    if n_clusters == 0 || n_clusters > nrows {
+        return Err(anyhow!("n_clusters must be between 1 and {}, got {}", nrows, n_clusters));
+    }
+    validate_row_lengths(data)?;
+    
+    let tolerance = tolerance.unwrap_or(1e-4);
+    let mut rng = Xoshiro256Plus::seed_from_u64(seed.unwrap_or(42));
+    let squared_distance = |a: &[f64], b: &[f64]| -> f64 { a.iter().zip(b.iter()).map(|(x, y)| (x - y).powi(2)).sum() };
+    
+    let seed_distance = |i: usize, j: usize| squared_distance(&data[i], &data[j]);
+    let seeds = kmeans_plus_plus_init(nrows, n_clusters, seed_distance, &mut rng);
+    let mut centroids: Vec<Vec<f64>> = seeds.into_iter().map(|i| data[i].clone()).collect();
+    
+    let (assignments, trajectory) = lloyd_iterations(
+        nrows,
+        &mut centroids,
+        max_iterations.unwrap_or(100),
+        tolerance,
+        |point, centroid: &Vec<f64>| squared_distance(&data[point], centroid),
+        |indices, centroid| {
+            let new_centroid = mean_of_rows(data, indices);
+            let shift = euclidean_distance(centroid, &new_centroid);
+            (new_centroid, shift)
+        },
+    );
+    
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (idx, &cluster) in assignments.iter().enumerate() {
+        clusters.entry(cluster).or_default().push(idx);
+    }
+    let degenerate_centroids = find_degenerate_centroids(&centroids);
+    
+    Ok((
+        ClusteringResult {
+            clusters,
+            outliers: Vec::new(),
+            assignments,
+            degenerate_centroids,
+            centroids: Some(centroids),
+        },
+        trajectory,
+    ))
+}
+
+/// Performs K-means style clustering under any of the crate's distance metrics
+///
+/// linfa's KMeans is Euclidean only, so this runs its own Lloyd loop with k-means++
+/// initialization (seeded by squared metric distances) and the update step that minimizes
+/// the chosen metric: the mean for Euclidean, the coordinate-wise median for Manhattan
+/// (k-medians) and the mean rescaled to unit length for Cosine (as in `spherical_kmeans`).
+/// DTW has no simple center update; use `kmedoids_clustering` for it.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to create
+/// * `metric` - The distance metric to use (`Euclidean`, `Manhattan` or `Cosine`)
+/// * `max_iterations` - Maximum number of iterations (default: 100)
+/// * `tolerance` - Stop when the total centroid shift under `metric` is below this
+///   (default: 1e-4)
+/// * `seed` - Random seed for reproducibility (default: 42)
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result, or error if the metric is DTW,
+///   `n_clusters` is out of range or the rows have different lengths
+pub fn kmeans_generic(
+    data: &[Vec<f64>],
+    n_clusters: usize,
+    metric: DistanceMetric,
+    max_iterations: Option<usize>,
+    tolerance: Option<f64>,
+    seed: Option<u64>,
+) -> Result<ClusteringResult> {
+    let nrows = data.len();
+    if nrows == 0 {
+        return Err(anyhow!("Empty input data"));
+    }
+    if n_clusters == 0 || n_clusters > nrows {
+        return Err(anyhow!("n_clusters must be between 1 and {}, got {}", nrows, n_clusters));
+    }
+    if matches!(metric, DistanceMetric::Dtw { .. }) {
+        return Err(anyhow!("kmeans_generic does not support DTW; use kmedoids_clustering"));
+    }
+    validate_row_lengths(data)?;
+    
+    let tolerance = tolerance.unwrap_or(1e-4);
+    let mut rng = Xoshiro256Plus::seed_from_u64(seed.unwrap_or(42));
+    
+    // Seeded by squared metric distances
+    let seed_distance = |i: usize, j: usize| metric.distance(&data[i], &data[j]).powi(2);
+    let seeds = kmeans_plus_plus_init(nrows, n_clusters, seed_distance, &mut rng);
+    let mut centroids: Vec<Vec<f64>> = seeds.into_iter().map(|i| data[i].clone()).collect();
+    
+    let center = |indices: &[usize]| -> Vec<f64> {
+        match metric {
+            DistanceMetric::Manhattan => {
+                let n_dims = data[indices[0]].len();
+                (0..n_dims)
+                    .map(|dim| {
+                        let mut values: Vec<f64> = indices.iter().map(|&idx| data[idx][dim]).collect();
+                        values.sort_by(|a, b| a.total_cmp(b));
+                        let mid = values.len() / 2;
+                        if values.len().is_multiple_of(2) {
+                            (values[mid - 1] + values[mid]) / 2.0
+                        } else {
+                            values[mid]
+                        }
+                    })
+                    .collect()
+            }
+            DistanceMetric::Cosine => {
+                let mean = mean_of_rows(data, indices);
+                let norm = mean.iter().map(|x| x * x).sum::<f64>().sqrt();
+                if norm > 0.0 {
+                    mean.into_iter().map(|x| x / norm).collect()
+                } else {
+                    mean
+                }
+            }
+            _ => mean_of_rows(data, indices),
+        }
+    };
+    
+    let (assignments, _) = lloyd_iterations(
+        nrows,
+        &mut centroids,
+        max_iterations.unwrap_or(100),
+        tolerance,
+        |point, centroid: &Vec<f64>| metric.distance(&data[point], centroid),
+        |indices, centroid| {
+            let new_centroid = center(indices);
+            let shift = metric.distance(centroid, &new_centroid);
+            (new_centroid, shift)
+        },
+    );
+    
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (idx, &cluster) in assignments.iter().enumerate() {
+        clusters.entry(cluster).or_default().push(idx);
+    }
+    let degenerate_centroids = find_degenerate_centroids(&centroids);
+    
+    Ok(ClusteringResult {
+        clusters,
+        outliers: Vec::new(),
+        assignments,
+        degenerate_centroids,
+        centroids: Some(centroids),
+    })
+}
+
+/// Compute the centroid (mean point) of every cluster in a clustering result
+///
+/// Outliers are not part of any cluster and are ignored.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+///
+/// # Returns
+/// * `HashMap<usize, Vec<f64>>` - Mapping of cluster IDs to their centroids
+pub fn compute_centroids(data: &[Vec<f64>], result: &ClusteringResult) -> HashMap<usize, Vec<f64>> {
+    result
+        .clusters
+        .iter()
+        .filter(|(_, indices)| !indices.is_empty())
+        .map(|(&cluster_id, indices)| (cluster_id, mean_of_rows(data, indices)))
+        .collect()
+}
+
+/// Compute the centroids of all clusters as a list ordered by cluster ID
+///
+/// Same centroids as `compute_centroids`, for APIs that take centers as a plain list, e.g.
+/// seeding a partitioning from the clusters HDBSCAN discovered (outliers are ignored).
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+///
+/// # Returns
+/// * `Vec<Vec<f64>>` - The centroids of the non-empty clusters, in ascending cluster ID order
+pub fn centroids_from_result(data: &[Vec<f64>], result: &ClusteringResult) -> Vec<Vec<f64>> {
+    let mut centroids: Vec<(usize, Vec<f64>)> = compute_centroids(data, result).into_iter().collect();
+    centroids.sort_unstable_by_key(|(cluster_id, _)| *cluster_id);
+    centroids.into_iter().map(|(_, centroid)| centroid).collect()
+}
+
+/// Compute the per-feature means of all clusters as a matrix
+///
+/// Matrix form of `compute_centroids`, e.g. for a clusters × features heatmap.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+///
+/// # Returns
+/// * `(Vec<usize>, Array2<f64>)` - The IDs of the non-empty clusters in ascending order, and
+///   the matrix whose row `i` holds the feature means of cluster `ids[i]`
+pub fn cluster_feature_means(data: &[Vec<f64>], result: &ClusteringResult) -> (Vec<usize>, Array2<f64>) {
+    let centroids = compute_centroids(data, result);
+    let mut ids: Vec<usize> = centroids.keys().cloned().collect();
+    ids.sort_unstable();
+    
+    let n_features = data.first().map_or(0, |point| point.len());
+    let mut means = Array2::<f64>::zeros((ids.len(), n_features));
+    for (row, cluster_id) in ids.iter().enumerate() {
+        for (feature, &mean) in centroids[cluster_id].iter().enumerate() {
+            means[[row, feature]] = mean;
+        }
+    }
+    (ids, means)
+}
+
+/// Compute the centroid-to-centroid distance matrix with the cluster IDs as labels
+///
+/// # Arguments
+/// * `centroids` - Mapping of cluster IDs to centroids (e.g. from `compute_centroids`)
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `(Vec<usize>, Array2<f64>)` - The cluster IDs in ascending order, and the symmetric
+///   distance matrix whose rows and columns follow that order
+pub fn centroid_distance_table(
+    centroids: &HashMap<usize, Vec<f64>>,
+    metric: DistanceMetric,
+) -> (Vec<usize>, Array2<f64>) {
+    let mut ids: Vec<usize> = centroids.keys().cloned().collect();
+    ids.sort_unstable();
+    
+    let ordered: Vec<Vec<f64>> = ids.iter().map(|id| centroids[id].clone()).collect();
+    (ids, pairwise_distances(&ordered, metric))
+}
+
+/// Assigns every point to its nearest centroid
+///
+/// Ties are broken deterministically: a point equidistant from several centroids goes to the
+/// one with the smallest index, so repeated runs always produce the same assignments.
+///
+/// # Arguments
+/// * `data` - The data points
+/// * `centroids` - Cluster centroids; a point's assignment is the index of its centroid
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `Result<Vec<usize>>` - Centroid index per point, or error if there are no centroids
+pub fn assign_to_nearest_centroid(
+    data: &[Vec<f64>],
+    centroids: &[Vec<f64>],
+    metric: DistanceMetric,
+) -> Result<Vec<usize>> {
+    if centroids.is_empty() {
+        return Err(anyhow!("At least one centroid is required"));
+    }
+    
+    Ok(data
+        .iter()
+        .map(|point| {
+            let mut best = (0, f64::INFINITY);
+            for (cluster, centroid) in centroids.iter().enumerate() {
+                // Strict comparison keeps the lowest index among equidistant centroids
+                let d = metric.distance(point, centroid);
+                if d < best.1 {
+                    best = (cluster, d);
+                }
+            }
+            best.0
+        })
+        .collect())
+}
+
+/// Computes a confidence score for the nearest-centroid assignment of every point
+///
+/// The score is `1 - d_nearest / d_second_nearest`, clamped to [0, 1]: close to 1 when the
+/// assigned centroid is much closer than the runner-up, 0 for points on the boundary between
+/// two clusters. A cheap alternative to GMM membership probabilities for hard clusterings.
+///
+/// # Arguments
+/// * `data` - The data points
+/// * `centroids` - Cluster centroids
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `Vec<f64>` - Confidence per point (1.0 for every point when there are fewer than two centroids)
+pub fn assignment_confidence(data: &[Vec<f64>], centroids: &[Vec<f64>], metric: DistanceMetric) -> Vec<f64> {
+    if centroids.len() < 2 {
+        return vec![1.0; data.len()];
+    }
+    
+    data.iter()
+        .map(|point| {
+            let mut nearest = f64::INFINITY;
+            let mut second = f64::INFINITY;
+            for centroid in centroids {
+                let d = metric.distance(point, centroid);
+                if d < nearest {
+                    second = nearest;
+                    nearest = d;
+                } else if d < second {
+                    second = d;
+                }
+            }
+            
+            if second > 0.0 {
+                (1.0 - nearest / second).clamp(0.0, 1.0)
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Finds the cluster whose centroid is closest to a batch of points as a whole
+///
+/// Useful to route a group of new points (e.g. a batch of documents) to an existing cluster.
+/// Ties go to the smallest centroid index, as in `assign_to_nearest_centroid`.
+///
+/// # Arguments
+/// * `batch` - The query points
+/// * `centroids` - Cluster centroids
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `Result<(usize, f64)>` - Index of the centroid minimizing the mean distance to the batch and
+///   that mean distance, or error if the batch or the centroids are empty
+pub fn match_batch_to_cluster(
+    batch: &[Vec<f64>],
+    centroids: &[Vec<f64>],
+    metric: DistanceMetric,
+) -> Result<(usize, f64)> {
+    if batch.is_empty() {
+        return Err(anyhow!("Empty batch"));
+    }
+    if centroids.is_empty() {
+        return Err(anyhow!("At least one centroid is required"));
+    }
+    
+    let mut best = (0, f64::INFINITY);
+    for (cluster, centroid) in centroids.iter().enumerate() {
+        let mean_distance =
+            batch.iter().map(|point| metric.distance(point, centroid)).sum::<f64>() / batch.len() as f64;
+        if mean_distance < best.1 {
+            best = (cluster, mean_distance);
+        }
+    }
+    
+    Ok(best)
+}
+
+/// Names clusters after their nearest labeled prototype point
+///
+/// Several clusters can receive the same label when they share a nearest prototype. Ties go
+/// to the first prototype in `prototypes`.
+///
+/// # Arguments
+/// * `centroids` - Cluster centroids indexed by cluster ID
+/// * `prototypes` - Labeled reference points, e.g. one per known topic
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `HashMap<usize, String>` - Mapping of cluster IDs to the label of their nearest prototype
+///   (empty if there are no prototypes)
+pub fn label_clusters_by_prototypes(
+    centroids: &[Vec<f64>],
+    prototypes: &[(String, Vec<f64>)],
+    metric: DistanceMetric,
+) -> HashMap<usize, String> {
+    centroids
+        .iter()
+        .enumerate()
+        .filter_map(|(cluster_id, centroid)| {
+            let mut best: Option<(&String, f64)> = None;
+            for (label, prototype) in prototypes {
+                let d = metric.distance(centroid, prototype);
+                if best.is_none_or(|(_, best_d)| d < best_d) {
+                    best = Some((label, d));
+                }
+            }
+            best.map(|(label, _)| (cluster_id, label.clone()))
+        })
+        .collect()
+}
+
+/// Merges clusters whose centroids are closer than a threshold
+///
+/// Repeatedly merges the closest pair of clusters while their centroid distance is below
+/// `threshold`, replacing the pair's centroids by their size-weighted mean. This is the usual
+/// cleanup after running K-means with a deliberately high `n_clusters`. The merged cluster keeps
+/// the smaller ID; outliers are unchanged.
+///
+/// The output's `centroids` are `centroids` with the merged means in place; an ID absorbed
+/// into another cluster keeps its last centroid, like an empty K-means cluster. Of the input's
+/// `degenerate_centroids`, only the pairs whose clusters both survive are kept.
+///
+/// # Arguments
+/// * `result` - The clustering result to merge
+/// * `centroids` - Centroids indexed by cluster ID (clusters without a centroid are never merged)
+/// * `threshold` - Centroid distance below which clusters are merged
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `ClusteringResult` - The merged clustering result, with its centroids
+pub fn merge_close_clusters(
+    result: &ClusteringResult,
+    centroids: &[Vec<f64>],
+    threshold: f64,
+    metric: DistanceMetric,
+) -> ClusteringResult {
+    let (mut mergeable, mut fixed): (HashMap<usize, Vec<usize>>, HashMap<usize, Vec<usize>>) = result
+        .clusters
+        .clone()
+        .into_iter()
+        .partition(|(cluster_id, _)| *cluster_id < centroids.len());
+    let mut centroid_map: HashMap<usize, Vec<f64>> = mergeable
+        .keys()
+        .map(|&cluster_id| (cluster_id, centroids[cluster_id].clone()))
+        .collect();
+    
+    merge_closest_centroids(&mut mergeable, &mut centroid_map, metric, |_, distance| distance < threshold);
+    
+    let mut merged_centroids = centroids.to_vec();
+    for (cluster_id, centroid) in centroid_map {
+        merged_centroids[cluster_id] = centroid;
+    }
+    
+    fixed.extend(mergeable);
+    let n_points = result.assignments.len();
+    let mut merged = ClusteringResult::from_clusters(fixed, result.outliers.clone(), n_points);
+    merged.degenerate_centroids = result
+        .degenerate_centroids
+        .iter()
+        .filter(|(a, b)| merged.clusters.contains_key(a) && merged.clusters.contains_key(b))
+        .copied()
+        .collect();
+    merged.centroids = Some(merged_centroids);
+    merged
+}
+
+/// Splits one cluster into sub-clusters, leaving the rest of the clustering untouched
+///
+/// Runs KMeans on the members of `cluster_id` only. The sub-clusters replace the original
+/// cluster and get fresh IDs following the largest existing one, so no other cluster is
+/// renumbered; outliers are unchanged. If `result` has centroids, the sub-clusters' KMeans
+/// centroids are added under their new IDs and the split cluster's ID keeps its last centroid,
+/// like an empty K-means cluster; degenerate pairs are carried over the same way.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result to refine
+/// * `cluster_id` - The cluster to split
+/// * `n_sub` - Number of sub-clusters to create
+/// * `seed` - Random seed for KMeans (default: 42)
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The refined clustering result, or error if the cluster does
+///   not exist or has fewer than `n_sub` members
+pub fn split_cluster(
+    data: &[Vec<f64>],
+    result: &ClusteringResult,
+    cluster_id: usize,
+    n_sub: usize,
+    seed: Option<u64>,
+) -> Result<ClusteringResult> {
+    let members = result
+        .clusters
+        .get(&cluster_id)
+        .ok_or_else(|| anyhow!("Cluster {} does not exist", cluster_id))?;
+    if n_sub == 0 || n_sub > members.len() {
+        return Err(anyhow!(
+            "Cannot split cluster {} of {} members into {} sub-clusters",
+            cluster_id,
+            members.len(),
+            n_sub
+        ));
+    }
+    
+    let member_data: Vec<Vec<f64>> = members.iter().map(|&idx| data[idx].clone()).collect();
+    let sub_result = kmeans_clustering(&member_data, n_sub, None, None, seed)?;
+    Ok(replace_with_sub_clusters(result, cluster_id, &sub_result))
+}
+
+/// Replaces a cluster by the clusters of `sub_result`, a clustering of its members only
+///
+/// See `split_cluster` for the new IDs, centroids and degenerate pairs.
+fn replace_with_sub_clusters(
+    result: &ClusteringResult,
+    cluster_id: usize,
+    sub_result: &ClusteringResult,
+) -> ClusteringResult {
+    let members = &result.clusters[&cluster_id];
+    let first_new_id = result.clusters.keys().max().map_or(0, |&max_id| max_id + 1);
+    let mut sub_ids: Vec<usize> = sub_result.clusters.keys().cloned().collect();
+    sub_ids.sort_unstable();
+    let new_id: HashMap<usize, usize> =
+        sub_ids.iter().enumerate().map(|(offset, &sub_id)| (sub_id, first_new_id + offset)).collect();
+    
+    let mut clusters = result.clusters.clone();
+    clusters.remove(&cluster_id);
+    for &sub_id in &sub_ids {
+        let indices = sub_result.clusters[&sub_id].iter().map(|&i| members[i]).collect();
+        clusters.insert(new_id[&sub_id], indices);
+    }
+    
+    let n_points = result.assignments.len();
+    let mut split = ClusteringResult::from_clusters(clusters, result.outliers.clone(), n_points);
+    split.degenerate_centroids = result
+        .degenerate_centroids
+        .iter()
+        .filter(|(a, b)| split.clusters.contains_key(a) && split.clusters.contains_key(b))
+        .copied()
+        .chain(
+            sub_result
+                .degenerate_centroids
+                .iter()
+                .filter_map(|(a, b)| Some((*new_id.get(a)?, *new_id.get(b)?))),
+        )
+        .collect();
+    split.centroids = result.centroids.as_ref().zip(sub_result.centroids.as_ref()).map(|(old, sub)| {
+        // Slots past the largest ID belong to empty clusters, whose IDs are now reused; IDs
+        // that never had a centroid get an empty one
+        let mut centroids = old.clone();
+        centroids.resize_with(first_new_id, Vec::new);
+        centroids.extend(sub_ids.iter().map(|&sub_id| sub[sub_id].clone()));
+        centroids
+    });
+    split
+}
+
+/// Repeatedly merges the two clusters whose centroids are closest, as long as
+/// `should_merge(current cluster count, closest distance)` returns true
+///
+/// The merged cluster keeps the smaller of the two IDs and a size-weighted mean centroid.
+/// Ties between equally close pairs go to the pair with the smallest IDs.
+fn merge_closest_centroids(
+    clusters: &mut HashMap<usize, Vec<usize>>,
+    centroids: &mut HashMap<usize, Vec<f64>>,
+    metric: DistanceMetric,
+    mut should_merge: impl FnMut(usize, f64) -> bool,
+) {
+    while clusters.len() > 1 {
+        let mut ids: Vec<usize> = clusters.keys().cloned().collect();
+        ids.sort_unstable();
+        
+        let mut closest: Option<(usize, usize, f64)> = None;
+        for (i, &a) in ids.iter().enumerate() {
+            for &b in ids[i + 1..].iter() {
+                let d = metric.distance(&centroids[&a], &centroids[&b]);
+                match closest {
+                    Some((_, _, best)) if d >= best => {}
+                    _ => closest = Some((a, b, d)),
+                }
+            }
+        }
+        
+        let (keep, absorb, distance) = closest.expect("at least two clusters");
+        if !should_merge(clusters.len(), distance) {
+            break;
+        }
+        
+        let absorbed_members = clusters.remove(&absorb).unwrap_or_default();
+        let absorbed_centroid = centroids.remove(&absorb).unwrap_or_default();
+        let kept_members = clusters.entry(keep).or_default();
+        let (n_keep, n_absorb) = (kept_members.len() as f64, absorbed_members.len() as f64);
+        if let Some(centroid) = centroids.get_mut(&keep) {
+            for (c, &x) in centroid.iter_mut().zip(absorbed_centroid.iter()) {
+                *c = (*c * n_keep + x * n_absorb) / (n_keep + n_absorb);
+            }
+        }
+        kept_members.extend(absorbed_members);
+    }
+}
+
+/// Cluster centroids maintained incrementally as assigned points arrive
+///
+/// Each centroid is a running mean, so points never need to be stored or re-read.
+#[derive(Debug, Clone, Default)]
+pub struct StreamingCentroids {
+    /// Mapping of cluster IDs to their current mean and number of points seen
+    means: HashMap<usize, (Vec<f64>, usize)>,
+}
+
+impl StreamingCentroids {
+    /// Creates an empty set of streaming centroids
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Adds a point to the running mean of its cluster
+    ///
+    /// # Arguments
+    /// * `point` - The data point
+    /// * `cluster` - The cluster ID the point is assigned to
+    ///
+    /// # Returns
+    /// * `Result<()>` - Error if the point's dimension differs from earlier points of the cluster
+    pub fn update(&mut self, point: &[f64], cluster: usize) -> Result<()> {
+        let (mean, count) = self
+            .means
+            .entry(cluster)
+            .or_insert_with(|| (vec![0.0; point.len()], 0));
+        if mean.len() != point.len() {
+            return Err(anyhow!(
+                "Point has dimension {}, but cluster {} has dimension {}",
+                point.len(),
+                cluster,
+                mean.len()
+            ));
+        }
+        
+        *count += 1;
+        let n = *count as f64;
+        for (m, &x) in mean.iter_mut().zip(point.iter()) {
+            *m += (x - *m) / n;
+        }
+        Ok(())
+    }
+    
+    /// Number of points seen so far for a cluster
+    pub fn count(&self, cluster: usize) -> usize {
+        self.means.get(&cluster).map_or(0, |(_, count)| *count)
+    }
+    
+    /// Current centroid of every cluster seen so far
+    ///
+    /// # Returns
+    /// * `HashMap<usize, Vec<f64>>` - Mapping of cluster IDs to their centroids
+    pub fn centroids(&self) -> HashMap<usize, Vec<f64>> {
+        self.means
+            .iter()
+            .map(|(&cluster, (mean, _))| (cluster, mean.clone()))
+            .collect()
+    }
+}
+
+/// Performs K-means clustering on sparse vectors
+///
+/// Runs Lloyd's algorithm with k-means++ initialization directly on the sparse input, so
+/// distances and dot products only visit nonzero entries. Centroids are kept dense, which
+/// needs `n_clusters * n_dims` values of memory. See `SparseVector` for when sparse input
+/// pays off.
+///
+/// # Arguments
+/// * `data` - Sparse data points to cluster (see `SparseVector`)
+/// * `n_dims` - Dimensionality of the data (every index must be below this)
+/// * `n_clusters` - Number of clusters to create
+/// * `max_iterations` - Maximum number of iterations (default: 100)
+/// * `tolerance` - Convergence tolerance on the total centroid movement (default: 1e-4)
+/// * `seed` - Random seed for reproducibility (default: 42)
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result or error
+pub fn kmeans_clustering_sparse(
+    data: &[SparseVector],
+    n_dims: usize,
+    n_clusters: usize,
+    max_iterations: Option<usize>,
+    tolerance: Option<f64>,
+    seed: Option<u64>,
+) -> Result<ClusteringResult> {
+    kmeans_clustering_sparse_with_rng(
+        data,
+        n_dims,
+        n_clusters,
+        max_iterations,
+        tolerance,
+        Xoshiro256Plus::seed_from_u64(seed.unwrap_or(42)),
+    )
+}
+
+/// Same as `kmeans_clustering_sparse`, with a caller-supplied random number generator instead of a seed
+///
+/// Lets a specific PRNG be used, e.g. to reproduce the initialization of another pipeline.
+/// `kmeans_clustering_sparse` is this function with `Xoshiro256Plus` seeded from `seed`.
+///
+/// # Arguments
+/// * `data` - Sparse data points to cluster (see `SparseVector`)
+/// * `n_dims` - Dimensionality of the data (every index must be below this)
+/// * `n_clusters` - Number of clusters to create
+/// * `max_iterations` - Maximum number of iterations (default: 100)
+/// * `tolerance` - Convergence tolerance on the total centroid movement (default: 1e-4)
+/// * `rng` - Random number generator driving the initialization
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result or error
+pub fn kmeans_clustering_sparse_with_rng<R: Rng>(
+    data: &[SparseVector],
+    n_dims: usize,
+    n_clusters: usize,
+    max_iterations: Option<usize>,
+    tolerance: Option<f64>,
+    mut rng: R,
 ) -> Result<ClusteringResult> {
-    // Convert data to ndarray format
     let nrows = data.len();
     if nrows == 0 {
         return Err(anyhow!("Empty input data"));
     }
+    if n_clusters == 0 || n_clusters > nrows {
+        return Err(anyhow!("n_clusters must be between 1 and {}, got {}", nrows, n_clusters));
+    }
+    if let Some((row, _)) = data
+        .iter()
+        .enumerate()
+        .find(|(_, v)| v.iter().any(|&(idx, _)| idx >= n_dims))
+    {
+        return Err(anyhow!("Row {} has an index outside of n_dims = {}", row, n_dims));
+    }
     
-    let ncols = data[0].len();
-    let flat_data: Vec<f64> = data.iter().flat_map(|v| v.iter().cloned()).collect();
+    let tolerance = tolerance.unwrap_or(1e-4);
+    let squared_norms: Vec<f64> = data.iter().map(|v| sparse_dot(v, v)).collect();
     
-    let data_array = Array2::from_shape_vec((nrows, ncols), flat_data)
-        .map_err(|e| anyhow!("Failed to reshape data: {}", e))?;
+    // Squared distance between a sparse point and a dense centroid
+    let squared_distance = |idx: usize, centroid: &[f64], centroid_sq_norm: f64| -> f64 {
+        let dot: f64 = data[idx].iter().map(|&(d, x)| x * centroid[d]).sum();
+        (squared_norms[idx] - 2.0 * dot + centroid_sq_norm).max(0.0)
+    };
+    let densify = |idx: usize| -> Vec<f64> {
+        let mut dense = vec![0.0; n_dims];
+        for &(d, x) in data[idx].iter() {
+            dense[d] = x;
+        }
+        dense
+    };
     
-    // Create HDBSCAN algorithm with parameters
-    let mut hdbscan = HDbscan {
-        eps: epsilon.unwrap_or(0.0001),
-        alpha: alpha.unwrap_or(1.0),
-        min_samples,
-        min_cluster_size,
-        metric: Euclidean::default(),
-        boruvka: true,
+    let seed_distance = |i: usize, j: usize| {
+        (squared_norms[i] - 2.0 * sparse_dot(&data[i], &data[j]) + squared_norms[j]).max(0.0)
     };
+    let seeds = kmeans_plus_plus_init(nrows, n_clusters, seed_distance, &mut rng);
+    // Centroids are kept with their squared norm
+    let mut centroids: Vec<(Vec<f64>, f64)> =
+        seeds.into_iter().map(|i| (densify(i), squared_norms[i])).collect();
     
-    // Perform clustering
-    let (clusters, outliers) = PetalFit::fit(&mut hdbscan, &data_array);
+    let (assignments, _) = lloyd_iterations(
+        nrows,
+        &mut centroids,
+        max_iterations.unwrap_or(100),
+        tolerance,
+        |idx, (centroid, sq_norm)| squared_distance(idx, centroid, *sq_norm),
+        |indices, (centroid, _)| {
+            let mut new_centroid = vec![0.0; n_dims];
+            for &idx in indices {
+                for &(d, x) in data[idx].iter() {
+                    new_centroid[d] += x;
+                }
+            }
+            new_centroid.iter_mut().for_each(|x| *x /= indices.len() as f64);
+            let sq_norm = new_centroid.iter().map(|x| x * x).sum();
+            let shift = euclidean_distance(centroid, &new_centroid);
+            ((new_centroid, sq_norm), shift)
+        },
+    );
+    let centroids: Vec<Vec<f64>> = centroids.into_iter().map(|(centroid, _)| centroid).collect();
     
-    // Create cluster assignments vector (0 is reserved for outliers)
-    let mut assignments = vec![0; nrows];
-    for (cluster_id, indices) in clusters.iter() {
-        for &idx in indices {
-            assignments[idx] = *cluster_id;
-        }
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (idx, &cluster) in assignments.iter().enumerate() {
+        clusters.entry(cluster).or_default().push(idx);
     }
+    let degenerate_centroids = find_degenerate_centroids(&centroids);
     
     Ok(ClusteringResult {
         clusters,
-        outliers,
+        outliers: Vec::new(),
         assignments,
+        degenerate_centroids,
+        centroids: Some(centroids),
     })
 }
 
-/// Performs GMM (Gaussian Mixture Model) clustering on a dataset
+/// Performs spherical K-means clustering on pre-normalized data
+///
+/// K-means under cosine similarity: points and centroids live on the unit sphere, each point
+/// joins the centroid with the highest cosine similarity (lowest index on ties) and centroids
+/// are the normalized means of their members. The usual choice for text embeddings and TF-IDF
+/// vectors. Centroids are seeded k-means++ style on cosine distance. Zero vectors have
+/// similarity 0 to every centroid.
 ///
 /// # Arguments
-/// * `data` - A 2D array of data points to cluster
+/// * `data` - The data points with their precomputed norms (see `Normalized`)
 /// * `n_clusters` - Number of clusters to create
-/// * `n_runs` - Number of runs to perform (default: 10)
-/// * `tolerance` - Convergence tolerance (default: 1e-4)
+/// * `max_iterations` - Maximum number of iterations (default: 100)
 /// * `seed` - Random seed for reproducibility (default: 42)
 ///
 /// # Returns
-/// * `Result<ClusteringResult>` - The clustering result or error
-pub fn gmm_clustering(
-    data: &[Vec<f64>],
+/// * `Result<ClusteringResult>` - The clustering result with cluster IDs `0..n_clusters`, or error
+pub fn spherical_kmeans(
+    data: &Normalized,
     n_clusters: usize,
-    n_runs: Option<usize>,
-    tolerance: Option<f64>,
+    max_iterations: Option<usize>,
     seed: Option<u64>,
 ) -> Result<ClusteringResult> {
-    // Check for empty data
     let nrows = data.len();
     if nrows == 0 {
         return Err(anyhow!("Empty input data"));
     }
+    if n_clusters == 0 || n_clusters > nrows {
+        return Err(anyhow!("n_clusters must be between 1 and {}, got {}", nrows, n_clusters));
+    }
     
-    // Convert data to ndarray format for linfa
-    let ncols = data[0].len();
-    let flat_data: Vec<f64> = data.iter().flat_map(|v| v.iter().cloned()).collect();
-    
-    let data_array = Array2::from_shape_vec((nrows, ncols), flat_data)
-        .map_err(|e| anyhow!("Failed to reshape data: {}", e))?;
+    let unit = data.unit_rows();
+    let mut rng = Xoshiro256Plus::seed_from_u64(seed.unwrap_or(42));
     
-    // Create dataset for GMM
-    let dataset = DatasetBase::from(data_array);
+    // k-means++ initialization on cosine distance
+    let seed_distance = |i: usize, j: usize| (1.0 - unit.row(i).dot(&unit.row(j))).max(0.0).powi(2);
+    let seeds = kmeans_plus_plus_init(nrows, n_clusters, seed_distance, &mut rng);
+    let mut centroids: Vec<Vec<f64>> = seeds.into_iter().map(|i| unit.row(i).to_vec()).collect();
     
-    // Initialize random number generator
-    let rng = Xoshiro256Plus::seed_from_u64(seed.unwrap_or(42));
+    // Negated similarity, so the nearest centroid is the most similar one; stop only once
+    // the assignments settle
+    let (assignments, _) = lloyd_iterations(
+        nrows,
+        &mut centroids,
+        max_iterations.unwrap_or(100),
+        0.0,
+        |point, centroid: &Vec<f64>| -unit.row(point).dot(&ArrayView1::from(centroid.as_slice())),
+        |indices, centroid| {
+            let mut sum = Array1::<f64>::zeros(unit.ncols());
+            for &idx in indices {
+                sum += &unit.row(idx);
+            }
+            let norm = sum.dot(&sum).sqrt();
+            if norm > 0.0 {
+                let new_centroid = (sum / norm).to_vec();
+                let shift = euclidean_distance(centroid, &new_centroid);
+                (new_centroid, shift)
+            } else {
+                (centroid.clone(), 0.0)
+            }
+        },
+    );
     
-    // Configure and run GMM
-    let gmm = GaussianMixtureModel::params(n_clusters)
-        .n_runs(n_runs.unwrap_or(10) as u64)
-        .tolerance(tolerance.unwrap_or(1e-4))
-        .with_rng(rng)
-        .fit(&dataset)
-        .map_err(|e| anyhow!("GMM fitting failed: {}", e))?;
+    let indices: Vec<usize> = (0..nrows).collect();
+    Ok(ClusteringResult {
+        clusters: group_by_cluster(&assignments, &indices),
+        outliers: Vec::new(),
+        assignments,
+        degenerate_centroids: find_degenerate_centroids(&centroids),
+        centroids: Some(centroids),
+    })
+}
+
+/// Combines several clusterings of the same data into a consensus clustering
+///
+/// Builds the co-association matrix, the fraction of clusterings in which each pair of points
+/// shares a cluster, and runs average-linkage agglomerative clustering on
+/// `1 - co_association`. Runs with different algorithms, seeds or parameters can be mixed;
+/// outliers never co-cluster with anything. Needs N² memory for the matrix.
+///
+/// # Arguments
+/// * `results` - The clusterings to combine, all over the same points
+/// * `n_clusters` - Number of consensus clusters
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The consensus clustering with cluster IDs `0..n_clusters`
+///   and no outliers, or error
+pub fn consensus_clustering(results: &[ClusteringResult], n_clusters: usize) -> Result<ClusteringResult> {
+    let first = results.first().ok_or_else(|| anyhow!("At least one clustering is required"))?;
+    let n_points = first.assignments.len();
+    if let Some(result) = results.iter().find(|r| r.assignments.len() != n_points) {
+        return Err(anyhow!(
+            "All clusterings must cover the same points, got {} and {}",
+            n_points,
+            result.assignments.len()
+        ));
+    }
     
-    // Get cluster assignments
-    let clustered_data = gmm.predict(dataset);
-    let targets = clustered_data.targets();
+    let mut distances = Array2::<f64>::ones((n_points, n_points));
+    let share = 1.0 / results.len() as f64;
+    for result in results {
+        for indices in result.clusters.values() {
+            for &i in indices {
+                for &j in indices {
+                    distances[[i, j]] -= share;
+                }
+            }
+        }
+    }
+    for i in 0..n_points {
+        distances[[i, i]] = 0.0;
+    }
     
-    // Convert to the ClusteringResult format
-    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
-    let mut assignments = vec![0; nrows];
+    let merges = linkage(&distances, Linkage::Average)?;
+    let assignments = cut_dendrogram(&merges, n_points, n_clusters)?;
+    let indices: Vec<usize> = (0..n_points).collect();
     
-    for (idx, &cluster_id) in targets.iter().enumerate() {
-        // Store assignment
-        let cluster_id_usize = cluster_id as usize;
-        assignments[idx] = cluster_id_usize;
-        
-        // Add to clusters map
-        clusters.entry(cluster_id_usize)
-            .or_insert_with(Vec::new)
-            .push(idx);
+    Ok(ClusteringResult {
+        clusters: group_by_cluster(&assignments, &indices),
+        outliers: Vec::new(),
+        assignments,
+        degenerate_centroids: Vec::new(),
+        centroids: None,
+    })
+}
+
+/// Performs agglomerative (hierarchical) clustering on a dataset
+///
+/// Starts from one cluster per point and repeatedly merges the two closest clusters under the
+/// `method` linkage rule, on Euclidean distances, then cuts the resulting dendrogram. To
+/// inspect the merges or try several cuts, build the dendrogram once with
+/// `agglomerative_dendrogram` and cut it with `cut_dendrogram` or `cut_dendrogram_at_height`.
+/// Needs the full pairwise distance matrix (8·N² bytes) and O(N²) time.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `cut` - Either the number of clusters to keep or the height at which to cut
+/// * `method` - The linkage rule
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering with cluster IDs `0, 1, ...` in order of
+///   first appearance and no outliers, or error
+pub fn agglomerative_clustering(
+    data: &[Vec<f64>],
+    cut: DendrogramCut,
+    method: Linkage,
+) -> Result<ClusteringResult> {
+    if data.is_empty() {
+        return Err(anyhow!("Empty input data"));
     }
+    let n_points = data.len();
     
-    // GMM assigns all points to clusters, so there are no outliers
-    let outliers = Vec::new();
+    let merges = agglomerative_dendrogram(data, method)?;
+    let assignments = match cut {
+        DendrogramCut::NClusters(n_clusters) => cut_dendrogram(&merges, n_points, n_clusters)?,
+        DendrogramCut::Height(height) => cut_dendrogram_at_height(&merges, n_points, height)?,
+    };
+    let indices: Vec<usize> = (0..n_points).collect();
     
     Ok(ClusteringResult {
-        clusters,
-        outliers,
+        clusters: group_by_cluster(&assignments, &indices),
+        outliers: Vec::new(),
         assignments,
+        degenerate_centroids: Vec::new(),
+        centroids: None,
     })
 }
 
-/// Performs K-means clustering on a dataset
+/// Performs K-medoids clustering on the input data
+///
+/// Each cluster is represented by one of its members (the medoid) instead of a coordinate
+/// average, so only distances between points are needed. This makes it usable with metrics
+/// for which averaging is meaningless, e.g. `DistanceMetric::Dtw` for time series. Medoids are
+/// seeded k-means++ style and refined by alternating assignment and medoid updates. Requires the
+/// full pairwise distance matrix (8·N² bytes).
 ///
 /// # Arguments
 /// * `data` - A 2D array of data points to cluster
-/// * `n_clusters` - Number of clusters to create
+/// * `n_clusters` - Number of clusters to form
+/// * `metric` - The distance metric to use
 /// * `max_iterations` - Maximum number of iterations (default: 100)
-/// * `tolerance` - Convergence tolerance (default: 1e-4)
-/// * `seed` - Random seed for reproducibility (default: 42)
+/// * `seed` - Random seed for medoid initialization (default: 42)
 ///
 /// # Returns
-/// * `Result<ClusteringResult>` - The clustering result or error
-pub fn kmeans_clustering(
+/// * `Result<ClusteringResult>` - The clustering result with cluster IDs `0..n_clusters`, or error
+pub fn kmedoids_clustering(
     data: &[Vec<f64>],
     n_clusters: usize,
+    metric: DistanceMetric,
     max_iterations: Option<usize>,
-    tolerance: Option<f64>,
     seed: Option<u64>,
 ) -> Result<ClusteringResult> {
-    // Check for empty data
+    kmedoids_clustering_with_rng(
+        data,
+        n_clusters,
+        metric,
+        max_iterations,
+        Xoshiro256Plus::seed_from_u64(seed.unwrap_or(42)),
+    )
+}
+
+/// Same as `kmedoids_clustering`, with a caller-supplied random number generator instead of a seed
+///
+/// Lets a specific PRNG be used, e.g. to reproduce the initialization of another pipeline.
+/// `kmedoids_clustering` is this function with `Xoshiro256Plus` seeded from `seed`.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `n_clusters` - Number of clusters to form
+/// * `metric` - The distance metric to use
+/// * `max_iterations` - Maximum number of iterations (default: 100)
+/// * `rng` - Random number generator driving the initialization
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result with cluster IDs `0..n_clusters`, or error
+pub fn kmedoids_clustering_with_rng<R: Rng>(
+    data: &[Vec<f64>],
+    n_clusters: usize,
+    metric: DistanceMetric,
+    max_iterations: Option<usize>,
+    mut rng: R,
+) -> Result<ClusteringResult> {
     let nrows = data.len();
     if nrows == 0 {
         return Err(anyhow!("Empty input data"));
     }
+    if n_clusters == 0 || n_clusters > nrows {
+        return Err(anyhow!("n_clusters must be between 1 and {}, got {}", nrows, n_clusters));
+    }
     
-    // Convert data to ndarray format for linfa
-    let ncols = data[0].len();
-    let flat_data: Vec<f64> = data.iter().flat_map(|v| v.iter().cloned()).collect();
-    
-    let data_array = Array2::from_shape_vec((nrows, ncols), flat_data)
-        .map_err(|e| anyhow!("Failed to reshape data: {}", e))?;
-    
-    // Create dataset for KMeans
-    let dataset = DatasetBase::from(data_array);
-    
-    // Initialize random number generator
-    let rng = Xoshiro256Plus::seed_from_u64(seed.unwrap_or(42));
+    let distances = pairwise_distances(data, metric);
     
-    // Configure and run KMeans
-    let kmeans = KMeans::params_with_rng(n_clusters, rng)
-        .max_n_iterations(max_iterations.unwrap_or(100) as u64)
-        .tolerance(tolerance.unwrap_or(1e-4))
-        .fit(&dataset)
-        .map_err(|e| anyhow!("KMeans fitting failed: {}", e))?;
+    // k-means++ style initialization on the distance matrix
+    let seed_distance = |i: usize, j: usize| distances[[i, j]].powi(2);
+    let mut medoids = kmeans_plus_plus_init(nrows, n_clusters, seed_distance, &mut rng);
     
-    // Get cluster assignments
-    let clustered_data = kmeans.predict(dataset);
-    let targets = clustered_data.targets();
+    let (assignments, _) = lloyd_iterations(
+        nrows,
+        &mut medoids,
+        max_iterations.unwrap_or(100),
+        0.0,
+        |i, &medoid| distances[[i, medoid]],
+        |indices, &medoid| {
+            // Move the medoid to the member minimizing the summed distance to its cluster
+            let cost = |candidate: usize| indices.iter().map(|&i| distances[[candidate, i]]).sum::<f64>();
+            let mut best = (medoid, cost(medoid));
+            for &candidate in indices {
+                let c = cost(candidate);
+                if c < best.1 {
+                    best = (candidate, c);
+                }
+            }
+            (best.0, distances[[medoid, best.0]])
+        },
+    );
     
-    // Convert to the ClusteringResult format
     let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
-    let mut assignments = vec![0; nrows];
-    
-    for (idx, &cluster_id) in targets.iter().enumerate() {
-        // Store assignment
-        let cluster_id_usize = cluster_id as usize;
-        assignments[idx] = cluster_id_usize;
-        
-        // Add to clusters map
-        clusters.entry(cluster_id_usize)
-            .or_insert_with(Vec::new)
-            .push(idx);
+    for (idx, &cluster) in assignments.iter().enumerate() {
+        clusters.entry(cluster).or_default().push(idx);
     }
     
-    // KMeans assigns all points to clusters, so there are no outliers
-    let outliers = Vec::new();
-    
     Ok(ClusteringResult {
         clusters,
-        outliers,
+        outliers: Vec::new(),
         assignments,
+        degenerate_centroids: Vec::new(),
+        centroids: Some(medoids.iter().map(|&m| data[m].clone()).collect()),
     })
 }
 
+/// Finds pairs of centroids that are closer than a small, scale-relative epsilon
+///
+/// The threshold is `1e-6 * (1 + largest centroid norm)`, so it adapts to the data scale.
+///
+/// # Arguments
+/// * `centroids` - Centroids indexed by cluster ID
+///
+/// # Returns
+/// * `Vec<(usize, usize)>` - Pairs `(i, j)` with `i < j` of collapsed centroids
+pub fn find_degenerate_centroids(centroids: &[Vec<f64>]) -> Vec<(usize, usize)> {
+    let largest_norm = centroids
+        .iter()
+        .map(|c| c.iter().map(|x| x * x).sum::<f64>().sqrt())
+        .fold(0.0, f64::max);
+    let epsilon = 1e-6 * (1.0 + largest_norm);
+    
+    let mut pairs = Vec::new();
+    for i in 0..centroids.len() {
+        for j in (i + 1)..centroids.len() {
+            if euclidean_distance(&centroids[i], &centroids[j]) < epsilon {
+                pairs.push((i, j));
+            }
+        }
+    }
+    pairs
+}
+
 /// Group items by their cluster assignment
 ///
 /// # Arguments
@@ -250,4 +3269,369 @@ pub fn group_by_cluster<T: Clone>(
     }
     
     result
-} 
\ No newline at end of file
+}
+
+/// `group_by_cluster` on the rayon thread pool
+///
+/// The items are bucketed per thread and the buckets merged in order, so every group keeps
+/// the input order of its items and the output equals that of `group_by_cluster`. Requires
+/// the `parallel` feature.
+///
+/// # Arguments
+/// * `cluster_assignments` - Vector of cluster assignments (index = data point, value = cluster ID)
+/// * `items` - Vector of items to group by cluster assignment
+///
+/// # Returns
+/// * `HashMap<usize, Vec<T>>` - Mapping of cluster IDs to vectors of items
+#[cfg(feature = "parallel")]
+pub fn par_group_by_cluster<T: Clone + Send + Sync>(
+    cluster_assignments: &[usize],
+    items: &[T],
+) -> HashMap<usize, Vec<T>> {
+    use rayon::prelude::*;
+    
+    if cluster_assignments.len() != items.len() {
+        return HashMap::new();
+    }
+    
+    cluster_assignments
+        .par_iter()
+        .zip(items.par_iter())
+        .fold(HashMap::new, |mut buckets: HashMap<usize, Vec<T>>, (&cluster, item)| {
+            buckets.entry(cluster).or_default().push(item.clone());
+            buckets
+        })
+        // rayon reduces adjacent chunks left to right, so appending keeps the input order
+        .reduce(HashMap::new, |mut left, right| {
+            for (cluster, items) in right {
+                left.entry(cluster).or_default().extend(items);
+            }
+            left
+        })
+}
+
+/// Cluster data points and return their associated items grouped by cluster
+///
+/// Outliers are not part of any cluster, so their items are left out.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster (the numeric features of the items)
+/// * `items` - Items associated with the data points, in the same order
+/// * `algo` - The clustering algorithm to use
+///
+/// # Returns
+/// * `Result<HashMap<usize, Vec<T>>>` - Mapping of cluster IDs to vectors of items, or error
+pub fn cluster_items<T: Clone>(
+    data: &[Vec<f64>],
+    items: &[T],
+    algo: Algorithm,
+) -> Result<HashMap<usize, Vec<T>>> {
+    if data.len() != items.len() {
+        return Err(anyhow!(
+            "Data has {} points but {} items were given",
+            data.len(),
+            items.len()
+        ));
+    }
+    
+    let result = run_clustering(data, algo)?;
+    Ok(result
+        .clusters
+        .iter()
+        .map(|(&cluster_id, indices)| (cluster_id, indices.iter().map(|&idx| items[idx].clone()).collect()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Builds a result from assignments, treating assignment 0 as an outlier
+    fn result_from_assignments(assignments: &[usize]) -> ClusteringResult {
+        let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut outliers = Vec::new();
+        for (idx, &cluster_id) in assignments.iter().enumerate() {
+            if cluster_id == 0 {
+                outliers.push(idx);
+            } else {
+                clusters.entry(cluster_id).or_default().push(idx);
+            }
+        }
+        ClusteringResult::from_clusters(clusters, outliers, assignments.len())
+    }
+    
+    #[test]
+    fn assignments_rle_round_trips() {
+        let cases: [&[usize]; 4] = [&[], &[3, 3, 3, 3], &[1, 1, 0, 2, 2, 2, 1, 0, 0], &[2, 1, 2]];
+        for assignments in cases {
+            let result = result_from_assignments(assignments);
+            assert_eq!(result.assignments, assignments);
+            assert_eq!(decode_assignments_rle(&result.assignments_rle()), assignments);
+        }
+        
+        assert!(result_from_assignments(&[]).assignments_rle().is_empty());
+        assert_eq!(result_from_assignments(&[3, 3, 3, 3]).assignments_rle(), vec![(3, 4)]);
+        assert_eq!(
+            result_from_assignments(&[1, 1, 0, 2, 2, 2, 1, 0, 0]).assignments_rle(),
+            vec![(1, 2), (0, 1), (2, 3), (1, 1), (0, 2)]
+        );
+    }
+    
+    #[test]
+    fn algorithm_metric_follows_hdbscan_setting() {
+        let hdbscan = Algorithm::Hdbscan {
+            min_cluster_size: 5,
+            min_samples: None,
+            metric: DistanceMetric::Cosine,
+        };
+        assert_eq!(hdbscan.metric(), DistanceMetric::Cosine);
+        assert_eq!(Algorithm::KMeans { n_clusters: 3, seed: None }.metric(), DistanceMetric::Euclidean);
+        assert_eq!(Algorithm::Gmm { n_clusters: 3, seed: None }.metric(), DistanceMetric::Euclidean);
+    }
+    
+    #[test]
+    fn nearest_centroid_ties_go_to_lowest_index() {
+        let centroids = vec![vec![2.0, 0.0], vec![0.0, 0.0], vec![1.0, 5.0]];
+        // Exactly halfway between centroids 0 and 1
+        let data = vec![vec![1.0, 0.0], vec![1.9, 0.0], vec![0.1, 0.0]];
+        let assignments = assign_to_nearest_centroid(&data, &centroids, DistanceMetric::Euclidean).unwrap();
+        assert_eq!(assignments, vec![0, 0, 1]);
+    }
+    
+    #[test]
+    fn kmeans_trajectory_ties_go_to_lowest_index() {
+        // Identical points make every centroid a tie for every point, whatever the seed
+        let data = vec![vec![1.0, 1.0]; 4];
+        for seed in 0..5 {
+            let (result, trajectory) = kmeans_with_trajectory(&data, 2, None, None, Some(seed)).unwrap();
+            assert_eq!(result.assignments, vec![0; 4]);
+            assert_eq!(result.clusters[&0], vec![0, 1, 2, 3]);
+            // One update moves nothing, and the reassignment after it ends the loop
+            assert_eq!(trajectory, vec![0.0, 0.0]);
+        }
+    }
+    
+    #[test]
+    fn kmeans_plus_plus_picks_distinct_seeds() {
+        // Two distinct locations, each repeated: the second seed must come from the other one
+        let data: Vec<Vec<f64>> = vec![vec![0.0], vec![0.0], vec![0.0], vec![9.0], vec![9.0]];
+        let squared_distance = |i: usize, j: usize| (data[i][0] - data[j][0]).powi(2);
+        for seed in 0..10 {
+            let mut rng = Xoshiro256Plus::seed_from_u64(seed);
+            let seeds = kmeans_plus_plus_init(data.len(), 2, squared_distance, &mut rng);
+            assert_ne!(data[seeds[0]], data[seeds[1]]);
+        }
+        
+        // Once every point coincides with a seed, unused points are taken in order
+        let mut rng = Xoshiro256Plus::seed_from_u64(0);
+        let seeds = kmeans_plus_plus_init(data.len(), 4, squared_distance, &mut rng);
+        let mut sorted = seeds.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 4);
+    }
+    
+    #[test]
+    fn lloyd_variants_recover_separated_groups() {
+        let data = vec![
+            vec![10.0, 1.0], vec![9.0, 1.5], vec![11.0, 0.5], vec![8.0, 1.0],
+            vec![1.0, 10.0], vec![1.5, 9.0], vec![0.5, 11.0], vec![1.0, 8.0],
+        ];
+        let sparse: Vec<SparseVector> = data.iter().map(|p| vec![(0, p[0]), (1, p[1])]).collect();
+        let separates = |assignments: &[usize]| {
+            assignments[..4].iter().all(|&a| a == assignments[0])
+                && assignments[4..].iter().all(|&a| a == assignments[4])
+                && assignments[0] != assignments[4]
+        };
+        
+        for seed in 0..5 {
+            let (result, trajectory) = kmeans_with_trajectory(&data, 2, None, None, Some(seed)).unwrap();
+            assert!(separates(&result.assignments));
+            assert!(trajectory.windows(2).all(|w| w[1] <= w[0] + 1e-9));
+            for metric in [DistanceMetric::Euclidean, DistanceMetric::Manhattan, DistanceMetric::Cosine] {
+                let result = kmeans_generic(&data, 2, metric, None, None, Some(seed)).unwrap();
+                assert!(separates(&result.assignments));
+                let result = kmedoids_clustering(&data, 2, metric, None, Some(seed)).unwrap();
+                assert!(separates(&result.assignments));
+            }
+            let result = kmeans_clustering_sparse(&sparse, 2, 2, None, None, Some(seed)).unwrap();
+            assert!(separates(&result.assignments));
+            let result = spherical_kmeans(&Normalized::new(&data).unwrap(), 2, None, Some(seed)).unwrap();
+            assert!(separates(&result.assignments));
+        }
+    }
+    
+    #[test]
+    fn kmeans_model_predict_checks_dimensions() {
+        let model = KMeansModel {
+            centroids: vec![vec![0.0, 0.0], vec![10.0, 10.0]],
+            clustering: ClusteringResult::from_clusters(HashMap::new(), Vec::new(), 0),
+        };
+        assert_eq!(model.predict(&[vec![9.0, 8.0], vec![1.0, 2.0]]).unwrap(), vec![1, 0]);
+        let error = model.predict(&[vec![9.0, 8.0], vec![1.0]]).unwrap_err();
+        assert_eq!(error.to_string(), "Point 1 has 1 dimensions, but the model was fitted on 2");
+    }
+    
+    #[test]
+    fn lloyd_iterations_reassign_after_last_update() {
+        let points = [0.0, 1.0, 10.0, 11.0];
+        let mut centroids = vec![0.0, 1.0];
+        let update = |members: &[usize], previous: &f64| {
+            let mean = members.iter().map(|&i| points[i]).sum::<f64>() / members.len() as f64;
+            (mean, (mean - previous).abs())
+        };
+        // The first update already shifts less than the tolerance, but moves point 1
+        let (assignments, trajectory) =
+            lloyd_iterations(points.len(), &mut centroids, 10, 100.0, |i, c| (points[i] - c).abs(), update);
+        
+        assert_eq!(assignments, vec![0, 0, 1, 1]);
+        assert_eq!(trajectory.len(), 2);
+        let nearest = |x: f64| if (x - centroids[0]).abs() <= (x - centroids[1]).abs() { 0 } else { 1 };
+        assert!(points.iter().zip(&assignments).all(|(&x, &a)| nearest(x) == a));
+    }
+    
+    #[test]
+    fn merge_close_clusters_keeps_centroids() {
+        let clusters = HashMap::from([(0, vec![0, 1]), (1, vec![2, 3, 4]), (2, vec![5])]);
+        let mut result = ClusteringResult::from_clusters(clusters, vec![6], 7);
+        result.degenerate_centroids = vec![(0, 1)];
+        let centroids = vec![vec![0.0], vec![1.0], vec![10.0]];
+        
+        let merged = merge_close_clusters(&result, &centroids, 2.0, DistanceMetric::Euclidean);
+        assert_eq!(merged.clusters.len(), 2);
+        assert_eq!(merged.clusters[&0], vec![0, 1, 2, 3, 4]);
+        assert_eq!(merged.centroids, Some(vec![vec![0.6], vec![1.0], vec![10.0]]));
+        assert!(merged.degenerate_centroids.is_empty());
+    }
+    
+    #[test]
+    fn split_cluster_adds_sub_centroids() {
+        let clusters = HashMap::from([(0, vec![0, 1, 2, 3]), (1, vec![4])]);
+        let mut result = ClusteringResult::from_clusters(clusters, vec![5], 6);
+        result.centroids = Some(vec![vec![5.0], vec![20.0], vec![30.0]]);
+        // Members 0 and 2, and members 1 and 3 of cluster 0
+        let sub_clusters = HashMap::from([(0, vec![0, 2]), (1, vec![1, 3])]);
+        let mut sub_result = ClusteringResult::from_clusters(sub_clusters, Vec::new(), 4);
+        sub_result.centroids = Some(vec![vec![1.0], vec![9.0]]);
+        
+        let split = replace_with_sub_clusters(&result, 0, &sub_result);
+        assert_eq!(split.clusters.len(), 3);
+        assert_eq!(split.clusters[&2], vec![0, 2]);
+        assert_eq!(split.clusters[&3], vec![1, 3]);
+        assert_eq!(split.assignments, vec![2, 3, 2, 3, 1, 0]);
+        assert_eq!(split.centroids, Some(vec![vec![5.0], vec![20.0], vec![1.0], vec![9.0]]));
+        
+        result.centroids = None;
+        assert_eq!(replace_with_sub_clusters(&result, 0, &sub_result).centroids, None);
+    }
+    
+    #[test]
+    fn kmeans_trajectory_rejects_ragged_rows() {
+        let data = vec![vec![0.0, 1.0], vec![2.0], vec![3.0, 4.0]];
+        assert!(kmeans_with_trajectory(&data, 2, None, None, None).is_err());
+    }
+    
+    #[test]
+    fn kmeans_generic_rejects_ragged_rows() {
+        let data = vec![vec![0.0, 1.0], vec![2.0], vec![3.0, 4.0]];
+        assert!(kmeans_generic(&data, 2, DistanceMetric::Manhattan, None, None, None).is_err());
+    }
+    
+    #[test]
+    fn group_by_cluster_keeps_input_order() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(5);
+        let assignments: Vec<usize> = (0..10_000).map(|_| rng.gen_range(0..7)).collect();
+        let items: Vec<usize> = (0..assignments.len()).collect();
+        
+        let grouped = group_by_cluster(&assignments, &items);
+        assert_eq!(grouped.values().map(Vec::len).sum::<usize>(), items.len());
+        for (cluster, members) in &grouped {
+            assert!(members.windows(2).all(|w| w[0] < w[1]));
+            assert!(members.iter().all(|&i| assignments[i] == *cluster));
+        }
+        assert!(group_by_cluster(&assignments[1..], &items).is_empty());
+        
+        #[cfg(feature = "parallel")]
+        {
+            assert_eq!(par_group_by_cluster(&assignments, &items), grouped);
+            assert!(par_group_by_cluster(&assignments[1..], &items).is_empty());
+        }
+    }
+    
+    #[test]
+    fn approximate_predict_uses_fit_metric() {
+        let train = vec![vec![1.0, 0.0], vec![1.1, 0.0], vec![0.0, 5.0], vec![0.0, 5.5]];
+        let clusters = HashMap::from([(1, vec![0, 1]), (2, vec![2, 3])]);
+        let result = ClusteringResult::from_clusters(clusters, Vec::new(), train.len());
+        // Closer to cluster 1 in Euclidean distance, but points in the direction of cluster 2
+        let new_point = vec![vec![0.5, 2.0]];
+        
+        let predict = |metric| hdbscan_approximate_predict(&train, &result, &new_point, 1, metric).unwrap();
+        assert_eq!(predict(DistanceMetric::Euclidean)[0].0, Some(1));
+        assert_eq!(predict(DistanceMetric::Cosine)[0].0, Some(2));
+    }
+    
+    #[test]
+    fn cosine_hdbscan_matches_brute_force_reference() {
+        // Three directions, each at norms from 1 to 50, so Euclidean neighbors are misleading
+        let mut rng = Xoshiro256Plus::seed_from_u64(11);
+        let data: Vec<Vec<f64>> = [0.0f64, 1.6, 3.5]
+            .iter()
+            .flat_map(|&angle| (0..12).map(move |i| (angle, 1.0 + 4.0 * i as f64)))
+            .map(|(angle, norm)| {
+                let angle = angle + rng.gen_range(-0.05..0.05);
+                vec![norm * angle.cos(), norm * angle.sin()]
+            })
+            .collect();
+        let (n, min_cluster_size, min_samples) = (data.len(), 7, 3);
+        
+        // Mutual reachability from fully sorted distances, clustered by plain single linkage
+        let distances = pairwise_distances(&data, DistanceMetric::Cosine);
+        let core: Vec<f64> = distances
+            .outer_iter()
+            .map(|row| {
+                let mut sorted = row.to_vec();
+                sorted.sort_by(f64::total_cmp);
+                sorted[min_samples - 1]
+            })
+            .collect();
+        let reachability = Array2::from_shape_fn((n, n), |(i, j)| {
+            if i == j { 0.0 } else { distances[[i, j]].max(core[i]).max(core[j]) }
+        });
+        let merges = linkage(&reachability, Linkage::Single).unwrap();
+        let expected = condense_tree(&merges, n, min_cluster_size).unwrap().select_clusters();
+        assert_eq!(expected, vec![(0..12).collect::<Vec<_>>(), (12..24).collect(), (24..36).collect()]);
+        
+        let params = HdbscanParams {
+            min_samples: Some(min_samples),
+            metric: DistanceMetric::Cosine,
+            ..Default::default()
+        };
+        let result = hdbscan_clustering_with_params(&data, min_cluster_size, params).unwrap();
+        let mut found: Vec<Vec<usize>> = result.clusters.into_values().collect();
+        found.sort_by_key(|points| points[0]);
+        assert_eq!(found, expected);
+        assert!(result.outliers.is_empty());
+        
+        let params = HdbscanParams { alpha: Some(0.5), ..params };
+        assert!(hdbscan_clustering_with_params(&data, min_cluster_size, params).is_err());
+    }
+    
+    #[test]
+    fn cosine_dbscan_uses_exact_neighborhoods() {
+        // Two directions at very different norms, a border point and a lone direction
+        let data = vec![
+            vec![1.0, 0.0], vec![10.0, 0.1], vec![50.0, 1.0], vec![3.0, 0.15],
+            vec![0.0, 2.0], vec![0.2, 20.0], vec![-0.5, 40.0],
+            vec![20.0, 2.2],
+            vec![-1.0, -1.0],
+        ];
+        let result = dbscan_clustering(&data, 0.01, 3, DistanceMetric::Cosine).unwrap();
+        
+        assert_eq!(result.clusters.len(), 2);
+        assert_eq!(result.clusters[&1], vec![0, 1, 2, 3, 7]);
+        assert_eq!(result.clusters[&2], vec![4, 5, 6]);
+        assert_eq!(result.outliers, vec![8]);
+        assert_eq!(result.assignments, vec![1, 1, 1, 1, 2, 2, 2, 1, 0]);
+    }
+}
@@ -1,12 +1,17 @@
 use anyhow::Result;
+use ndarray::{Array1, Array2, Axis};
 use std::collections::HashSet;
 use rand::seq::SliceRandom;
+use rand::Rng;
 use rand_xoshiro::rand_core::SeedableRng;
 use rand_xoshiro::Xoshiro256Plus;
 use hnsw_rs::prelude::*;
 use annembed::prelude::*;
 use annembed::fromhnsw::kgraph::{kgraph_from_hnsw_all, KGraph};
 
+use crate::neighbors::k_nearest_neighbors;
+use crate::utils::{euclidean_distance, validate_row_lengths, vec_to_array2};
+
 /// Result structure returned by dimensionality reduction functions
 #[derive(Clone, Debug)]
 pub struct EmbeddingResult {
@@ -16,8 +21,92 @@ pub struct EmbeddingResult {
     pub original_indices: Vec<usize>,
 }
 
+impl EmbeddingResult {
+    /// Flattens the embeddings in column-major order, e.g. for upload to a GPU buffer
+    ///
+    /// For N points of dimension D the output has N·D values: the first coordinate of every
+    /// point in point order, then the second coordinate of every point, and so on. The value
+    /// at `dim * N + point` is `embeddings[point][dim]`. The dimension is taken from the
+    /// first point.
+    pub fn to_column_major(&self) -> Vec<f64> {
+        let n_dims = self.embeddings.first().map_or(0, |e| e.len());
+        (0..n_dims)
+            .flat_map(|dim| self.embeddings.iter().map(move |e| e[dim]))
+            .collect()
+    }
+}
+
+/// Options of the dimensionality reduction
+///
+/// Tuning parameters of the HNSW graph and the annembed optimization, plus the optional
+/// preprocessing, weighting and reproducibility settings. The tuning defaults suit datasets of
+/// thousands of points or more. Small datasets (a few hundred points) usually embed better
+/// with fewer graph neighbors (`knbn`) and more gradient batches (`nb_grad_batch`). Override
+/// single fields with `DimReductionParams { knbn: 4, ..Default::default() }`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DimReductionParams<'a> {
+    /// Size of the candidate list while building the HNSW graph; larger is more accurate but
+    /// slower (default: 50)
+    pub ef_c: usize,
+    /// Maximum number of connections per HNSW node (default: 70)
+    pub max_nb_connection: usize,
+    /// Number of neighbors per node in the k-graph that is embedded (default: 6)
+    pub knbn: usize,
+    /// Number of gradient batches annembed runs (default: 30)
+    pub nb_grad_batch: usize,
+    /// Center the embedding at the origin and scale each axis to unit standard deviation,
+    /// making embeddings comparable across runs (default: `false`)
+    pub normalize_output: bool,
+    /// Optional per-dimension weights; each coordinate is multiplied by its weight before the
+    /// L2 distances of the HNSW graph are computed. This is a diagonal Mahalanobis metric with
+    /// the squared weights on the diagonal, emphasizing informative features (default: none)
+    pub dimension_weights: Option<&'a [f64]>,
+    /// Optional number of PCA components to project onto before building the HNSW graph.
+    /// Cheaper distances make this much faster on very high-dimensional data, at the cost of
+    /// discarding the variance outside the kept components, which can merge neighborhoods that
+    /// only differ along the dropped directions (default: none)
+    pub pca_preprocess: Option<usize>,
+    /// Insert points into the HNSW graph one by one in input order and run the embedding on a
+    /// one-thread rayon pool, so the result no longer depends on thread scheduling. Slower
+    /// (default: `false`)
+    pub deterministic: bool,
+    /// Optional importance weight per input point. annembed has no notion of node weights, so
+    /// this is approximated by inserting each point `max(1, round(weight))` times into the HNSW
+    /// graph: the copies attract the neighborhoods of nearby points, letting heavy points
+    /// anchor the layout. Only the first copy is returned. The graph grows with the total
+    /// weight, and copies sit at zero distance from each other, so keep weights small
+    /// (default: none)
+    pub point_weights: Option<&'a [f64]>,
+    /// Random seed for choosing the `sample_size` subset (default: 42). Neither hnsw_rs nor
+    /// annembed takes a seed: the HNSW layer draws and annembed's initialization and edge
+    /// sampling use the crates' own generators. With `deterministic` set, repeated runs with
+    /// the same seed, parameters and input are identical as long as those generators are
+    /// fixed-seeded
+    pub seed: Option<u64>,
+}
+
+impl Default for DimReductionParams<'_> {
+    fn default() -> Self {
+        DimReductionParams {
+            ef_c: 50,
+            max_nb_connection: 70,
+            knbn: 6,
+            nb_grad_batch: 30,
+            normalize_output: false,
+            dimension_weights: None,
+            pca_preprocess: None,
+            deterministic: false,
+            point_weights: None,
+            seed: None,
+        }
+    }
+}
+
 /// Performs dimensionality reduction on input data using HNSW and Annembed
 ///
+/// Runs with `DimReductionParams::default()`; use `perform_dimension_reduction_with_params`
+/// to tune the graph, weight dimensions or points, or make the embedding reproducible.
+///
 /// # Arguments
 /// * `input_data` - A slice of vectors representing the high-dimensional data points
 /// * `output_dim` - The target dimensionality to reduce to
@@ -30,9 +119,114 @@ pub fn perform_dimension_reduction(
     output_dim: usize,
     sample_size: Option<usize>,
 ) -> Result<EmbeddingResult, Box<dyn std::error::Error>> {
-    let (data_to_use, original_indices) = if let Some(size) = sample_size {
+    let params = DimReductionParams::default();
+    perform_dimension_reduction_with_params(input_data, output_dim, sample_size, params)
+}
+
+/// Performs dimensionality reduction with custom options
+///
+/// # Arguments
+/// * `input_data` - A slice of vectors representing the high-dimensional data points
+/// * `output_dim` - The target dimensionality to reduce to
+/// * `sample_size` - Optional parameter to use only a subset of data for faster computation
+/// * `params` - Graph and embedding parameters, preprocessing, weights and seed (see
+///   `DimReductionParams`)
+///
+/// # Returns
+/// * `Result<EmbeddingResult, Box<dyn std::error::Error>>` - The reduced embeddings and original
+///   indices, or error if a tuning parameter is zero or a weight vector has the wrong length
+pub fn perform_dimension_reduction_with_params(
+    input_data: &[Vec<f64>],
+    output_dim: usize,
+    sample_size: Option<usize>,
+    params: DimReductionParams,
+) -> Result<EmbeddingResult, Box<dyn std::error::Error>> {
+    perform_dimension_reduction_with_diagnostics(input_data, output_dim, sample_size, params)
+        .map(|(result, _)| result)
+}
+
+/// Embeds data with the default settings and returns the annembed diagnostics alongside
+///
+/// Shorthand for `perform_dimension_reduction_with_diagnostics` without sampling and with
+/// `DimReductionParams::default()`, for comparing parameter settings by a number rather
+/// than by eye. annembed does not report its final cross-entropy cost, so the comparable
+/// scalar is `EmbeddingDiagnostics::quality_estimate`.
+///
+/// # Arguments
+/// * `input_data` - A slice of vectors representing the high-dimensional data points
+/// * `output_dim` - The target dimensionality to reduce to
+///
+/// # Returns
+/// * `Result<(EmbeddingResult, EmbeddingDiagnostics), Box<dyn std::error::Error>>` - The reduced
+///   embeddings and original indices, and the embedding diagnostics
+pub fn embed_with_diagnostics(
+    input_data: &[Vec<f64>],
+    output_dim: usize,
+) -> Result<(EmbeddingResult, EmbeddingDiagnostics), Box<dyn std::error::Error>> {
+    perform_dimension_reduction_with_diagnostics(input_data, output_dim, None, DimReductionParams::default())
+}
+
+/// Performs dimensionality reduction and reports how the embedding optimization went
+///
+/// Same as `perform_dimension_reduction_with_params`, which delegates here, plus an
+/// `EmbeddingDiagnostics` describing the annembed run.
+///
+/// # Arguments
+/// * `input_data` - A slice of vectors representing the high-dimensional data points
+/// * `output_dim` - The target dimensionality to reduce to
+/// * `sample_size` - Optional parameter to use only a subset of data for faster computation
+/// * `params` - Graph and embedding parameters, preprocessing, weights and seed (see
+///   `DimReductionParams`)
+///
+/// # Returns
+/// * `Result<(EmbeddingResult, EmbeddingDiagnostics), Box<dyn std::error::Error>>` - The reduced
+///   embeddings and original indices, and the embedding diagnostics
+pub fn perform_dimension_reduction_with_diagnostics(
+    input_data: &[Vec<f64>],
+    output_dim: usize,
+    sample_size: Option<usize>,
+    params: DimReductionParams,
+) -> Result<(EmbeddingResult, EmbeddingDiagnostics), Box<dyn std::error::Error>> {
+    let DimReductionParams {
+        normalize_output,
+        dimension_weights,
+        pca_preprocess,
+        deterministic,
+        point_weights,
+        seed,
+        ..
+    } = params;
+    if params.ef_c == 0 || params.max_nb_connection == 0 || params.knbn == 0 || params.nb_grad_batch == 0 {
+        return Err(anyhow::anyhow!("DimReductionParams fields must be positive, got {:?}", params).into());
+    }
+    validate_row_lengths(input_data)?;
+    if let Some(weights) = point_weights {
+        if weights.len() != input_data.len() {
+            return Err(anyhow::anyhow!(
+                "point_weights has length {}, expected {} points",
+                weights.len(),
+                input_data.len()
+            )
+            .into());
+        }
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err(anyhow::anyhow!("point_weights must be finite and non-negative").into());
+        }
+    }
+    if let (Some(weights), Some(first)) = (dimension_weights, input_data.first()) {
+        if weights.len() != first.len() {
+            return Err(anyhow::anyhow!(
+                "dimension_weights has length {}, expected input dimension {}",
+                weights.len(),
+                first.len()
+            )
+            .into());
+        }
+    }
+    
+    let (mut data_to_use, original_indices) = if let Some(size) = sample_size {
         let size = std::cmp::min(size, input_data.len());
-        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let mut rng = Xoshiro256Plus::seed_from_u64(seed.unwrap_or(42));
         let mut indices: Vec<usize> = (0..input_data.len()).collect();
         indices.shuffle(&mut rng);
         let sample_indices = indices[0..size].to_vec();
@@ -54,32 +248,188 @@ pub fn perform_dimension_reduction(
         )
     };
 
+    if let Some(weights) = dimension_weights {
+        for point in data_to_use.iter_mut() {
+            for (x, &w) in point.iter_mut().zip(weights.iter()) {
+                *x *= w;
+            }
+        }
+    }
+
+    if let Some(n_components) = pca_preprocess {
+        data_to_use = pca_reduction(&data_to_use, n_components)?.embeddings;
+    }
+
+    // Every point once with its own id, then the extra copies of weighted points with ids
+    // past the end, so the first `n_points` rows of the embedding are the real points
+    let n_points = data_to_use.len();
+    let mut data_with_id: Vec<(&Vec<f64>, usize)> = data_to_use.iter().zip(0..n_points).collect();
+    if let Some(weights) = point_weights {
+        for (point, &original_idx) in data_to_use.iter().zip(original_indices.iter()) {
+            let copies = weights[original_idx].round().max(1.0) as usize;
+            for _ in 1..copies {
+                data_with_id.push((point, data_with_id.len()));
+            }
+        }
+    }
+
     // Create HNSW index
-    let ef_c = 50;
-    let max_nb_connection = 70;
-    let nb_layer = 16.min((data_to_use.len() as f64).ln().trunc() as usize);
+    let nb_layer = 16.min((data_with_id.len() as f64).ln().trunc() as usize);
     
     let hnsw = Hnsw::<f64, DistL2>::new(
-        max_nb_connection,
-        data_to_use.len(),
+        params.max_nb_connection,
+        data_with_id.len(),
         nb_layer,
-        ef_c,
+        params.ef_c,
         DistL2 {},
     );
 
     // Insert data into HNSW
-    let data_with_id: Vec<(&Vec<f64>, usize)> =
-        data_to_use.iter().zip(0..data_to_use.len()).collect();
-    hnsw.parallel_insert(&data_with_id);
+    let (mut embeddings, diagnostics) = if deterministic {
+        for &(point, id) in data_with_id.iter() {
+            hnsw.insert((point.as_slice(), id));
+        }
+        embed_single_threaded(&hnsw, output_dim, params)?
+    } else {
+        hnsw.parallel_insert(&data_with_id);
+        embed_hnsw(&hnsw, output_dim, params)?
+    };
+    embeddings.truncate(n_points);
+    
+    if normalize_output {
+        normalize_embeddings(&mut embeddings);
+    }
+
+    Ok((
+        EmbeddingResult {
+            embeddings,
+            original_indices,
+        },
+        diagnostics,
+    ))
+} 
 
+/// An HNSW index that keeps growing as points arrive and can be re-embedded at any time
+///
+/// Adding points only inserts them into the existing graph, so periodic re-embedding avoids
+/// rebuilding the index from scratch as `perform_dimension_reduction` does on every call.
+/// Points are identified by their insertion order.
+pub struct EmbeddingIndex {
+    hnsw: Hnsw<'static, f64, DistL2>,
+    n_points: usize,
+    dim: Option<usize>,
+}
+
+impl EmbeddingIndex {
+    /// Creates an empty index
+    ///
+    /// # Arguments
+    /// * `expected_size` - Expected final number of points, used to size the HNSW layers
+    pub fn new(expected_size: usize) -> Self {
+        let DimReductionParams { ef_c, max_nb_connection, .. } = DimReductionParams::default();
+        let nb_layer = 16.min((expected_size.max(1) as f64).ln().trunc() as usize).max(1);
+        
+        EmbeddingIndex {
+            hnsw: Hnsw::<f64, DistL2>::new(max_nb_connection, expected_size, nb_layer, ef_c, DistL2 {}),
+            n_points: 0,
+            dim: None,
+        }
+    }
+    
+    /// Inserts new points into the index
+    ///
+    /// # Arguments
+    /// * `new` - The points to add; they get the next indices in insertion order
+    ///
+    /// # Returns
+    /// * `Result<(), Box<dyn std::error::Error>>` - Error if a point's dimension differs from the index
+    pub fn add_points(&mut self, new: &[Vec<f64>]) -> Result<(), Box<dyn std::error::Error>> {
+        // Nothing to insert, and no first point to fix the dimension from
+        let Some(first) = new.first() else {
+            return Ok(());
+        };
+        let dim = *self.dim.get_or_insert(first.len());
+        if let Some((i, point)) = new.iter().enumerate().find(|(_, p)| p.len() != dim) {
+            return Err(anyhow::anyhow!("Point {} has length {}, expected {}", i, point.len(), dim).into());
+        }
+        
+        let data_with_id: Vec<(&Vec<f64>, usize)> = new.iter().zip(self.n_points..).collect();
+        self.hnsw.parallel_insert(&data_with_id);
+        self.n_points += new.len();
+        Ok(())
+    }
+    
+    /// Number of points in the index
+    pub fn len(&self) -> usize {
+        self.n_points
+    }
+    
+    /// Whether the index holds no points
+    pub fn is_empty(&self) -> bool {
+        self.n_points == 0
+    }
+    
+    /// Embeds all points currently in the index
+    ///
+    /// # Arguments
+    /// * `output_dim` - The target dimensionality to reduce to
+    ///
+    /// # Returns
+    /// * `Result<EmbeddingResult, Box<dyn std::error::Error>>` - The embeddings, indexed by insertion order
+    pub fn reembed(&self, output_dim: usize) -> Result<EmbeddingResult, Box<dyn std::error::Error>> {
+        Ok(EmbeddingResult {
+            embeddings: embed_hnsw(&self.hnsw, output_dim, DimReductionParams::default())?.0,
+            original_indices: (0..self.n_points).collect(),
+        })
+    }
+}
+
+/// Diagnostics of the annembed optimization behind an embedding
+///
+/// annembed does not expose its final cross-entropy cost or gradient norm (they are only
+/// logged), so this reports what is available: the gradient budget and annembed's own
+/// edge-length based quality estimate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EmbeddingDiagnostics {
+    /// Number of gradient batches the embedder ran
+    pub nb_grad_batch: usize,
+    /// Whether the whole gradient budget was used. annembed has no early stopping, so this is
+    /// always `true`; if layouts look unconverged, raise `nb_grad_batch`
+    pub budget_exhausted: bool,
+    /// annembed's quality estimate, comparing the lengths of the embedded neighbor-graph edges
+    /// with the original ones (`None` if annembed could not compute it)
+    pub quality_estimate: Option<f64>,
+    /// Number of neighbors per node in the graph that was embedded and that
+    /// `quality_estimate` was computed on; only compare estimates with equal values
+    pub n_graph_neighbors: usize,
+}
+
+/// Runs `embed_hnsw` on a one-thread rayon pool, so annembed's parallel loops run in a fixed order
+fn embed_single_threaded(
+    hnsw: &Hnsw<f64, DistL2>,
+    output_dim: usize,
+    params: DimReductionParams,
+) -> Result<(Vec<Vec<f64>>, EmbeddingDiagnostics)> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()?
+        .install(|| embed_hnsw(hnsw, output_dim, params))
+}
+
+/// Builds the k-graph of an HNSW index and embeds it with annembed
+fn embed_hnsw(
+    hnsw: &Hnsw<f64, DistL2>,
+    output_dim: usize,
+    params: DimReductionParams,
+) -> Result<(Vec<Vec<f64>>, EmbeddingDiagnostics)> {
     // Create KGraph
-    let knbn = 6;
-    let kgraph: KGraph<f64> = kgraph_from_hnsw_all(&hnsw, knbn)
+    let knbn = params.knbn;
+    let kgraph: KGraph<f64> = kgraph_from_hnsw_all(hnsw, knbn)
         .map_err(|e| anyhow::anyhow!("Failed to create KGraph: {}", e))?;
 
     // Set up Embedder
     let mut embed_params = EmbedderParams::default();
-    embed_params.nb_grad_batch = 30;
+    embed_params.nb_grad_batch = params.nb_grad_batch;
     embed_params.scale_rho = 1.;
     embed_params.beta = 1.;
     embed_params.grad_step = 1.;
@@ -91,12 +441,752 @@ pub fn perform_dimension_reduction(
     embedder.embed()
         .map_err(|e| anyhow::anyhow!("Failed to embed: {}", e))?;
 
+    let diagnostics = EmbeddingDiagnostics {
+        nb_grad_batch: embed_params.nb_grad_batch,
+        budget_exhausted: true,
+        quality_estimate: embedder.get_quality_estimate_from_edge_length(knbn),
+        n_graph_neighbors: knbn,
+    };
+
     // Get embedded data
     let embedded_data = embedder.get_embedded_reindexed();
-    let embeddings: Vec<Vec<f64>> = embedded_data.outer_iter().map(|row| row.to_vec()).collect();
+    Ok((embedded_data.outer_iter().map(|row| row.to_vec()).collect(), diagnostics))
+}
+
+/// Projects data onto its leading principal components (PCA)
+///
+/// The principal directions are found by orthogonal (subspace) iteration on the centered
+/// data, which avoids forming the full covariance matrix and needs no LAPACK backend. The
+/// sign of each component is fixed so that its largest loading is positive, making the
+/// output deterministic.
+///
+/// # Arguments
+/// * `input_data` - A slice of vectors representing the data points
+/// * `n_components` - Number of principal components to keep
+///
+/// # Returns
+/// * `Result<EmbeddingResult, Box<dyn std::error::Error>>` - The projected data, in order of
+///   decreasing explained variance, with `original_indices` covering every input point
+pub fn pca_reduction(
+    input_data: &[Vec<f64>],
+    n_components: usize,
+) -> Result<EmbeddingResult, Box<dyn std::error::Error>> {
+    let (projected, _) = principal_components(input_data, n_components)?;
+    
+    Ok(EmbeddingResult {
+        embeddings: projected.outer_iter().map(|row| row.to_vec()).collect(),
+        original_indices: (0..input_data.len()).collect(),
+    })
+}
 
+/// Projects data onto the fewest principal components that retain a share of the variance
+///
+/// Computes every principal component, then keeps the smallest number whose cumulative
+/// explained variance ratio reaches `min_explained`, replacing the manual scree-plot step.
+/// The number of kept components is the dimension of the returned embeddings. Constant data
+/// keeps one component.
+///
+/// # Arguments
+/// * `data` - A slice of vectors representing the data points
+/// * `min_explained` - Share of the total variance to retain, in (0, 1] (e.g. 0.95)
+///
+/// # Returns
+/// * `Result<EmbeddingResult, Box<dyn std::error::Error>>` - The projected data, in order of
+///   decreasing explained variance, with `original_indices` covering every input point
+pub fn pca_reduction_variance(
+    data: &[Vec<f64>],
+    min_explained: f64,
+) -> Result<EmbeddingResult, Box<dyn std::error::Error>> {
+    if !(min_explained > 0.0 && min_explained <= 1.0) {
+        return Err(anyhow::anyhow!("min_explained must be in (0, 1], got {}", min_explained).into());
+    }
+    let max_components = data.len().min(data.first().map_or(0, |v| v.len()));
+    let (projected, variances) = principal_components(data, max_components)?;
+    
+    let total: f64 = variances.iter().sum();
+    let mut cumulative = 0.0;
+    let n_components = variances
+        .iter()
+        .position(|&v| {
+            cumulative += v;
+            total <= 0.0 || cumulative / total >= min_explained - 1e-12
+        })
+        .map_or(max_components, |idx| idx + 1);
+    
     Ok(EmbeddingResult {
-        embeddings,
-        original_indices,
+        embeddings: projected
+            .outer_iter()
+            .map(|row| row.iter().take(n_components).cloned().collect())
+            .collect(),
+        original_indices: (0..data.len()).collect(),
     })
-} 
\ No newline at end of file
+}
+
+/// Computes the projection of `input_data` onto its top `n_components` principal components,
+/// along with the variance explained by each component
+fn principal_components(
+    input_data: &[Vec<f64>],
+    n_components: usize,
+) -> Result<(Array2<f64>, Vec<f64>), Box<dyn std::error::Error>> {
+    let n = input_data.len();
+    if n < 2 {
+        return Err(anyhow::anyhow!("PCA needs at least 2 data points, got {}", n).into());
+    }
+    let dim = validate_row_lengths(input_data)?;
+    if n_components == 0 || n_components > dim.min(n) {
+        return Err(anyhow::anyhow!(
+            "n_components must be between 1 and {}, got {}",
+            dim.min(n),
+            n_components
+        )
+        .into());
+    }
+    
+    let mut x = vec_to_array2(input_data)?;
+    let mean: Array1<f64> = x.mean_axis(Axis(0)).expect("non-empty data");
+    x -= &mean;
+    
+    let mut rng = Xoshiro256Plus::seed_from_u64(42);
+    let mut basis = Array2::from_shape_fn((dim, n_components), |_| rng.gen::<f64>() - 0.5);
+    orthonormalize_columns(&mut basis);
+    
+    let scale = (n - 1) as f64;
+    let mut variances = vec![0.0; n_components];
+    for _ in 0..200 {
+        let mut next = x.t().dot(&x.dot(&basis)) / scale;
+        let next_variances: Vec<f64> = (0..n_components)
+            .map(|j| basis.column(j).dot(&next.column(j)))
+            .collect();
+        orthonormalize_columns(&mut next);
+        basis = next;
+        
+        let converged = variances
+            .iter()
+            .zip(next_variances.iter())
+            .all(|(old, new)| (old - new).abs() <= 1e-10 * new.abs().max(1e-300));
+        variances = next_variances;
+        if converged {
+            break;
+        }
+    }
+    
+    // Order components by explained variance and fix their signs
+    let mut order: Vec<usize> = (0..n_components).collect();
+    order.sort_by(|&a, &b| variances[b].total_cmp(&variances[a]));
+    let mut components = Array2::zeros((dim, n_components));
+    for (target, &source) in order.iter().enumerate() {
+        let mut column = basis.column(source).to_owned();
+        let largest = column.iter().cloned().fold(0.0, |acc: f64, v| if v.abs() > acc.abs() { v } else { acc });
+        if largest < 0.0 {
+            column.mapv_inplace(|v| -v);
+        }
+        components.column_mut(target).assign(&column);
+    }
+    let variances = order.iter().map(|&j| variances[j].max(0.0)).collect();
+    
+    Ok((x.dot(&components), variances))
+}
+
+/// Orthonormalizes the columns of a matrix in place with modified Gram-Schmidt
+///
+/// Columns that become (numerically) zero are replaced by the first canonical basis vector
+/// that is still linearly independent of the previous columns.
+fn orthonormalize_columns(matrix: &mut Array2<f64>) {
+    let (dim, n_cols) = matrix.dim();
+    let mut fallback = 0;
+    for j in 0..n_cols {
+        loop {
+            for prev in 0..j {
+                let projection = matrix.column(prev).dot(&matrix.column(j));
+                let prev_column = matrix.column(prev).to_owned();
+                matrix.column_mut(j).scaled_add(-projection, &prev_column);
+            }
+            
+            let norm = matrix.column(j).dot(&matrix.column(j)).sqrt();
+            if norm > 1e-12 || fallback >= dim {
+                if norm > 0.0 {
+                    matrix.column_mut(j).mapv_inplace(|v| v / norm);
+                }
+                break;
+            }
+            
+            matrix.column_mut(j).fill(0.0);
+            matrix[[fallback, j]] = 1.0;
+            fallback += 1;
+        }
+    }
+}
+
+/// Aligns an embedding onto a reference embedding of the same points (Procrustes analysis)
+///
+/// Embeddings of the same data from different runs are often rotated or reflected copies of
+/// each other. This centers both, finds the orthogonal matrix (rotation or reflection) that
+/// best maps the target onto the reference in the least-squares sense, from the SVD of
+/// `targetᵀ · reference`, and moves the result to the reference's centroid. Scale is left
+/// unchanged, so distances within the target are preserved.
+///
+/// # Arguments
+/// * `reference` - The embedding to align to
+/// * `target` - The embedding to align, with the same points in the same order
+///
+/// # Returns
+/// * `Result<Vec<Vec<f64>>, Box<dyn std::error::Error>>` - The aligned target, or error if the
+///   two embeddings differ in size or dimensionality
+pub fn align_embeddings(
+    reference: &[Vec<f64>],
+    target: &[Vec<f64>],
+) -> Result<Vec<Vec<f64>>, Box<dyn std::error::Error>> {
+    if reference.len() != target.len() {
+        return Err(anyhow::anyhow!(
+            "reference has {} points but target has {}",
+            reference.len(),
+            target.len()
+        )
+        .into());
+    }
+    if reference.is_empty() {
+        return Ok(Vec::new());
+    }
+    let dim = reference[0].len();
+    if reference.iter().chain(target.iter()).any(|point| point.len() != dim) {
+        return Err(anyhow::anyhow!("All points of both embeddings must have {} dimensions", dim).into());
+    }
+    
+    let reference = vec_to_array2(reference)?;
+    let target = vec_to_array2(target)?;
+    let reference_mean = reference.mean_axis(Axis(0)).expect("non-empty embedding");
+    let target_mean = target.mean_axis(Axis(0)).expect("non-empty embedding");
+    let centered_reference = &reference - &reference_mean;
+    let centered_target = &target - &target_mean;
+    
+    // SVD of M = targetᵀ · reference via the eigendecomposition of MᵀM = V Σ² Vᵀ
+    let m = centered_target.t().dot(&centered_reference);
+    let (eigenvalues, eigenvectors) = symmetric_eigen(m.t().dot(&m));
+    let mut order: Vec<usize> = (0..dim).collect();
+    order.sort_by(|&a, &b| eigenvalues[b].total_cmp(&eigenvalues[a]));
+    let v = eigenvectors.select(Axis(1), &order);
+    
+    let largest = order.first().map_or(0.0, |&first| eigenvalues[first].max(0.0).sqrt());
+    let mut u = Array2::<f64>::zeros((dim, dim));
+    for (i, &component) in order.iter().enumerate() {
+        let sigma = eigenvalues[component].max(0.0).sqrt();
+        if sigma > 1e-12 * largest {
+            u.column_mut(i).assign(&(m.dot(&v.column(i)) / sigma));
+        }
+    }
+    // Directions with zero singular value (sorted last) are arbitrary; complete U to an
+    // orthonormal basis
+    orthonormalize_columns(&mut u);
+    
+    let rotation = u.dot(&v.t());
+    let aligned = centered_target.dot(&rotation) + &reference_mean;
+    Ok(aligned.outer_iter().map(|row| row.to_vec()).collect())
+}
+
+/// Eigendecomposition of a symmetric matrix by cyclic Jacobi rotations
+///
+/// Returns the eigenvalues and the matrix whose columns are the matching unit eigenvectors.
+/// Meant for the small matrices of embedding dimensions.
+fn symmetric_eigen(mut a: Array2<f64>) -> (Vec<f64>, Array2<f64>) {
+    let n = a.nrows();
+    let mut vectors = Array2::<f64>::eye(n);
+    let scale = a.iter().map(|x| x * x).sum::<f64>();
+    
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|p| (0..n).filter(move |&q| q != p).map(move |q| (p, q)))
+            .map(|(p, q)| a[[p, q]].powi(2))
+            .sum();
+        if off_diagonal <= 1e-24 * scale {
+            break;
+        }
+        
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[[p, q]] == 0.0 {
+                    continue;
+                }
+                let theta = (a[[q, q]] - a[[p, p]]) / (2.0 * a[[p, q]]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                
+                for k in 0..n {
+                    let (akp, akq) = (a[[k, p]], a[[k, q]]);
+                    a[[k, p]] = c * akp - s * akq;
+                    a[[k, q]] = s * akp + c * akq;
+                }
+                for k in 0..n {
+                    let (apk, aqk) = (a[[p, k]], a[[q, k]]);
+                    a[[p, k]] = c * apk - s * aqk;
+                    a[[q, k]] = s * apk + c * aqk;
+                }
+                for k in 0..n {
+                    let (vkp, vkq) = (vectors[[k, p]], vectors[[k, q]]);
+                    vectors[[k, p]] = c * vkp - s * vkq;
+                    vectors[[k, q]] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+    
+    ((0..n).map(|i| a[[i, i]]).collect(), vectors)
+}
+
+/// Measures how well an embedding preserves local neighborhoods (trustworthiness)
+///
+/// Penalizes points that are among the `k` nearest neighbors in the embedding but not in the
+/// original space, in proportion to how far down the original ranking they are. A score of
+/// 1.0 means no false neighbors were introduced. Computes exact ranks: O(N² log N) time.
+///
+/// # Arguments
+/// * `high_dim` - The original data points
+/// * `low_dim` - The embedded data points, in the same order
+/// * `k` - Neighborhood size (must satisfy `3k < 2N - 1`)
+///
+/// # Returns
+/// * `Result<f64, Box<dyn std::error::Error>>` - Trustworthiness in [0, 1] (higher is better)
+pub fn trustworthiness(
+    high_dim: &[Vec<f64>],
+    low_dim: &[Vec<f64>],
+    k: usize,
+) -> Result<f64, Box<dyn std::error::Error>> {
+    Ok(trustworthiness_curve(high_dim, low_dim, &[k])?[0])
+}
+
+/// Computes trustworthiness at several neighborhood sizes
+///
+/// Plotting the curve shows whether small neighborhoods (local structure) or large ones
+/// (global structure) are better preserved. Ranks are computed once for all `k` values.
+///
+/// # Arguments
+/// * `high_dim` - The original data points
+/// * `low_dim` - The embedded data points, in the same order
+/// * `k_values` - Neighborhood sizes (each must satisfy `3k < 2N - 1`)
+///
+/// # Returns
+/// * `Result<Vec<f64>, Box<dyn std::error::Error>>` - Trustworthiness per entry of `k_values`
+pub fn trustworthiness_curve(
+    high_dim: &[Vec<f64>],
+    low_dim: &[Vec<f64>],
+    k_values: &[usize],
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let penalties = trustworthiness_penalties(high_dim, low_dim, k_values)?;
+    let n = high_dim.len() as f64;
+    
+    Ok(k_values
+        .iter()
+        .enumerate()
+        .map(|(k_idx, &k)| {
+            let k = k as f64;
+            let total: f64 = penalties.iter().map(|p| p[k_idx]).sum();
+            1.0 - 2.0 / (n * k * (2.0 * n - 3.0 * k - 1.0)) * total
+        })
+        .collect())
+}
+
+/// Decomposes trustworthiness into a score per point
+///
+/// Each point's score uses the same normalization as the global measure, so the mean of
+/// the returned scores equals `trustworthiness(high_dim, low_dim, k)`. Low scores mark
+/// points whose embedding neighborhood contains points that are far away in the original space.
+///
+/// # Arguments
+/// * `high_dim` - The original data points
+/// * `low_dim` - The embedded data points, in the same order
+/// * `k` - Neighborhood size (must satisfy `3k < 2N - 1`)
+///
+/// # Returns
+/// * `Result<Vec<f64>, Box<dyn std::error::Error>>` - Score per point (higher is better, at most 1.0)
+pub fn per_point_trustworthiness(
+    high_dim: &[Vec<f64>],
+    low_dim: &[Vec<f64>],
+    k: usize,
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let penalties = trustworthiness_penalties(high_dim, low_dim, &[k])?;
+    let n = high_dim.len() as f64;
+    let k = k as f64;
+    let scale = 2.0 / (k * (2.0 * n - 3.0 * k - 1.0));
+    
+    Ok(penalties.iter().map(|p| 1.0 - scale * p[0]).collect())
+}
+
+/// Per point and per `k` in `k_values`, the trustworthiness penalty: the sum of
+/// `rank - k` over the point's embedding neighbors that are not among its `k` nearest
+/// neighbors in the original space
+fn trustworthiness_penalties(
+    high_dim: &[Vec<f64>],
+    low_dim: &[Vec<f64>],
+    k_values: &[usize],
+) -> Result<Vec<Vec<f64>>, Box<dyn std::error::Error>> {
+    let n = high_dim.len();
+    if n != low_dim.len() {
+        return Err(anyhow::anyhow!(
+            "high_dim has {} points but low_dim has {}",
+            n,
+            low_dim.len()
+        )
+        .into());
+    }
+    if let Some(&k) = k_values.iter().find(|&&k| k == 0 || 3 * k + 1 >= 2 * n) {
+        return Err(anyhow::anyhow!("k = {} is invalid for {} points (need 1 <= 3k < 2N - 1)", k, n).into());
+    }
+    
+    // Indices of all other points ordered by distance (ties broken by index)
+    let neighbor_order = |data: &[Vec<f64>], i: usize| -> Vec<usize> {
+        let mut order: Vec<(usize, f64)> = (0..n)
+            .filter(|&j| j != i)
+            .map(|j| (j, euclidean_distance(&data[i], &data[j])))
+            .collect();
+        order.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        order.into_iter().map(|(j, _)| j).collect()
+    };
+    
+    let mut rank = vec![0usize; n];
+    let penalties = (0..n)
+        .map(|i| {
+            for (r, j) in neighbor_order(high_dim, i).into_iter().enumerate() {
+                rank[j] = r + 1;
+            }
+            let low_order = neighbor_order(low_dim, i);
+            
+            k_values
+                .iter()
+                .map(|&k| {
+                    low_order[..k]
+                        .iter()
+                        .filter(|&&j| rank[j] > k)
+                        .map(|&j| (rank[j] - k) as f64)
+                        .sum()
+                })
+                .collect()
+        })
+        .collect();
+    
+    Ok(penalties)
+}
+
+/// Estimates the intrinsic dimensionality of a dataset
+///
+/// Uses the Levina-Bickel maximum likelihood estimator based on ratios of k-nearest-neighbor
+/// distances, averaged over points as suggested by MacKay and Ghahramani. Points with
+/// duplicate neighbors (zero distances) are skipped. A result well above the intended
+/// `output_dim` of `perform_dimension_reduction` means the reduction will lose structure.
+///
+/// # Arguments
+/// * `data` - A slice of vectors representing the data points
+/// * `k` - Number of nearest neighbors to use per point (at least 2; 10-20 is typical)
+///
+/// # Returns
+/// * `Result<f64, Box<dyn std::error::Error>>` - The estimated intrinsic dimension
+pub fn intrinsic_dimension(data: &[Vec<f64>], k: usize) -> Result<f64, Box<dyn std::error::Error>> {
+    if k < 2 {
+        return Err(anyhow::anyhow!("k must be at least 2").into());
+    }
+    if data.len() <= k {
+        return Err(anyhow::anyhow!("Need more than k = {} data points, got {}", k, data.len()).into());
+    }
+    
+    let neighbors = k_nearest_neighbors(data, k)?;
+    
+    let mut inverse_sum = 0.0;
+    let mut n_used = 0;
+    for point_neighbors in neighbors.iter() {
+        if point_neighbors.len() < k || point_neighbors[0].1 <= 0.0 {
+            continue;
+        }
+        
+        let t_k = point_neighbors[k - 1].1;
+        let log_ratio_sum: f64 = point_neighbors[..k - 1]
+            .iter()
+            .map(|&(_, t_j)| (t_k / t_j).ln())
+            .sum();
+        inverse_sum += log_ratio_sum / (k - 1) as f64;
+        n_used += 1;
+    }
+    
+    if n_used == 0 || inverse_sum <= 0.0 {
+        return Err(anyhow::anyhow!("Cannot estimate intrinsic dimension: too many duplicate points").into());
+    }
+    
+    Ok(n_used as f64 / inverse_sum)
+}
+
+/// Compares average shortest path lengths in the k-NN graphs before and after reduction
+///
+/// Builds the undirected k-NN graph of each point set and runs a breadth-first search from
+/// every point, so path lengths count hops. The ratio of the mean path length in the
+/// embedding to the one in the original data is a global structure measure that complements
+/// the local `trustworthiness`: values far from 1.0 mean the embedding stretched (> 1) or
+/// shortcut (< 1) long-range distances. Only pairs connected in both graphs are averaged.
+/// Costs O(N²·k) time.
+///
+/// # Arguments
+/// * `high_dim` - The original data points
+/// * `low_dim` - The embedded data points, in the same order
+/// * `k` - Number of nearest neighbors per point in both graphs
+///
+/// # Returns
+/// * `Result<f64, Box<dyn std::error::Error>>` - Mean low-dimensional path length divided by the
+///   mean high-dimensional one, or error if no pair is connected in both graphs
+pub fn graph_path_length_ratio(
+    high_dim: &[Vec<f64>],
+    low_dim: &[Vec<f64>],
+    k: usize,
+) -> Result<f64, Box<dyn std::error::Error>> {
+    let n = high_dim.len();
+    if n != low_dim.len() {
+        return Err(anyhow::anyhow!("high_dim has {} points but low_dim has {}", n, low_dim.len()).into());
+    }
+    if k == 0 || k >= n {
+        return Err(anyhow::anyhow!("k = {} is invalid for {} points (need 1 <= k < N)", k, n).into());
+    }
+    
+    let adjacency = |data: &[Vec<f64>]| -> Result<Vec<Vec<usize>>> {
+        let mut graph = vec![Vec::new(); n];
+        for (i, point_neighbors) in k_nearest_neighbors(data, k)?.into_iter().enumerate() {
+            for (j, _) in point_neighbors {
+                graph[i].push(j);
+                graph[j].push(i);
+            }
+        }
+        Ok(graph)
+    };
+    let high_graph = adjacency(high_dim)?;
+    let low_graph = adjacency(low_dim)?;
+    
+    let hops_from = |graph: &[Vec<usize>], source: usize| -> Vec<Option<usize>> {
+        let mut hops = vec![None; n];
+        hops[source] = Some(0);
+        let mut queue = std::collections::VecDeque::from([source]);
+        while let Some(i) = queue.pop_front() {
+            let next = hops[i].map(|h| h + 1);
+            for &j in &graph[i] {
+                if hops[j].is_none() {
+                    hops[j] = next;
+                    queue.push_back(j);
+                }
+            }
+        }
+        hops
+    };
+    
+    let (mut high_total, mut low_total) = (0usize, 0usize);
+    for source in 0..n {
+        let high_hops = hops_from(&high_graph, source);
+        let low_hops = hops_from(&low_graph, source);
+        for (high, low) in high_hops.into_iter().zip(low_hops).skip(source + 1) {
+            if let (Some(high), Some(low)) = (high, low) {
+                high_total += high;
+                low_total += low;
+            }
+        }
+    }
+    
+    if high_total == 0 {
+        return Err(anyhow::anyhow!("No pair of points is connected in both k-NN graphs").into());
+    }
+    Ok(low_total as f64 / high_total as f64)
+}
+
+/// Estimates the density of a 2D embedding on a regular grid, e.g. for a heatmap overlay
+///
+/// Gaussian kernel density estimate evaluated at `resolution × resolution` points spanning
+/// the bounding box of the embedding (edges included). The estimate integrates to 1 over
+/// the plane, so values are comparable between grids of the same embedding. Costs
+/// O(resolution² · N).
+///
+/// # Arguments
+/// * `embeddings` - The 2D embedded points
+/// * `resolution` - Number of grid points along each axis
+/// * `bandwidth` - Standard deviation of the Gaussian kernel, in embedding units
+///
+/// # Returns
+/// * `Result<Array2<f64>, Box<dyn std::error::Error>>` - The density grid, where entry
+///   `[row, col]` is at the `row`-th y value and `col`-th x value, both ascending (flip the
+///   rows for image coordinates), or error if the embedding is empty or not 2D
+pub fn embedding_density_grid(
+    embeddings: &[Vec<f64>],
+    resolution: usize,
+    bandwidth: f64,
+) -> Result<Array2<f64>, Box<dyn std::error::Error>> {
+    if embeddings.is_empty() {
+        return Err(anyhow::anyhow!("Empty embedding").into());
+    }
+    if let Some(point) = embeddings.iter().find(|point| point.len() != 2) {
+        return Err(anyhow::anyhow!("Density grids need 2D embeddings, got a {}D point", point.len()).into());
+    }
+    if resolution == 0 {
+        return Err(anyhow::anyhow!("resolution must be at least 1").into());
+    }
+    if bandwidth.is_nan() || bandwidth <= 0.0 {
+        return Err(anyhow::anyhow!("bandwidth must be positive, got {}", bandwidth).into());
+    }
+    
+    let axis = |dim: usize| -> Vec<f64> {
+        let min = embeddings.iter().map(|e| e[dim]).fold(f64::INFINITY, f64::min);
+        let max = embeddings.iter().map(|e| e[dim]).fold(f64::NEG_INFINITY, f64::max);
+        if resolution == 1 {
+            return vec![(min + max) / 2.0];
+        }
+        let step = (max - min) / (resolution - 1) as f64;
+        (0..resolution).map(|i| min + i as f64 * step).collect()
+    };
+    let (xs, ys) = (axis(0), axis(1));
+    
+    let variance = bandwidth * bandwidth;
+    let normalization = 1.0 / (embeddings.len() as f64 * 2.0 * std::f64::consts::PI * variance);
+    Ok(Array2::from_shape_fn((resolution, resolution), |(row, col)| {
+        let density: f64 = embeddings
+            .iter()
+            .map(|e| {
+                let squared_distance = (e[0] - xs[col]).powi(2) + (e[1] - ys[row]).powi(2);
+                (-squared_distance / (2.0 * variance)).exp()
+            })
+            .sum();
+        density * normalization
+    }))
+}
+
+/// Scores how far new points lie from the manifold captured by an embedding
+///
+/// Each query is placed in the embedding by k-NN interpolation: the inverse-distance
+/// weighted mean of the embedded positions of its `k` nearest training points (found
+/// exactly, in the original space). For a point on the manifold, the distances from that
+/// position to the embedded neighbors, times the scale of the embedding, match its original
+/// distances to the same neighbors. The scale is the ratio of summed original to summed
+/// embedded distances over the training k-NN pairs. The error is the root mean square
+/// mismatch, in original-space units.
+///
+/// This is an approximation: interpolation always lands among the neighbors, so a point far
+/// from every training point gets small embedded distances but large original ones, and
+/// thus a large error. A single global scale also assumes the embedding distorts distances
+/// evenly; compare errors against those of held-out points from the training distribution
+/// rather than using an absolute threshold. Training neighbors come from the approximate
+/// HNSW search of `k_nearest_neighbors`; each query costs O(N·D).
+///
+/// # Arguments
+/// * `train_high` - The original data points that were embedded
+/// * `train_low` - Their embedded positions, in the same order
+/// * `query_high` - The new points to score, in the original space
+/// * `k` - Number of nearest training points used for the interpolation and the comparison
+///
+/// # Returns
+/// * `Result<Vec<f64>, Box<dyn std::error::Error>>` - Reconstruction error per query point
+///   (larger means further off the manifold), or error if the inputs are inconsistent
+pub fn embedding_reconstruction_error(
+    train_high: &[Vec<f64>],
+    train_low: &[Vec<f64>],
+    query_high: &[Vec<f64>],
+    k: usize,
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let n = train_high.len();
+    if n != train_low.len() {
+        return Err(anyhow::anyhow!(
+            "train_high has {} points but train_low has {}",
+            n,
+            train_low.len()
+        )
+        .into());
+    }
+    if k == 0 || k >= n {
+        return Err(anyhow::anyhow!("k = {} is invalid for {} points (need 1 <= k < N)", k, n).into());
+    }
+    let dim = train_high[0].len();
+    if let Some(point) = query_high.iter().find(|point| point.len() != dim) {
+        return Err(anyhow::anyhow!("Query point has {} dimensions, expected {}", point.len(), dim).into());
+    }
+    
+    let (mut high_sum, mut low_sum) = (0.0, 0.0);
+    for (i, point_neighbors) in k_nearest_neighbors(train_high, k)?.into_iter().enumerate() {
+        for (j, distance) in point_neighbors {
+            high_sum += distance;
+            low_sum += euclidean_distance(&train_low[i], &train_low[j]);
+        }
+    }
+    let scale = if low_sum > 0.0 { high_sum / low_sum } else { 0.0 };
+    
+    Ok(query_high
+        .iter()
+        .map(|query| {
+            let mut neighbors: Vec<(usize, f64)> = train_high
+                .iter()
+                .enumerate()
+                .map(|(j, point)| (j, euclidean_distance(query, point)))
+                .collect();
+            neighbors.select_nth_unstable_by(k - 1, |a, b| a.1.total_cmp(&b.1));
+            neighbors.truncate(k);
+            
+            // An exact match sits at its neighbor's position; otherwise weight by 1/distance
+            let position = match neighbors.iter().find(|(_, d)| *d == 0.0) {
+                Some(&(j, _)) => train_low[j].clone(),
+                None => {
+                    let total: f64 = neighbors.iter().map(|(_, d)| 1.0 / d).sum();
+                    let mut position = vec![0.0; train_low[0].len()];
+                    for &(j, d) in &neighbors {
+                        for (p, &x) in position.iter_mut().zip(train_low[j].iter()) {
+                            *p += x / d / total;
+                        }
+                    }
+                    position
+                }
+            };
+            
+            let squared_error: f64 = neighbors
+                .iter()
+                .map(|&(j, d)| (d - scale * euclidean_distance(&position, &train_low[j])).powi(2))
+                .sum();
+            (squared_error / k as f64).sqrt()
+        })
+        .collect())
+}
+
+/// Centers embeddings at the origin and scales each axis to unit standard deviation
+///
+/// Axes with zero variance are only centered.
+fn normalize_embeddings(embeddings: &mut [Vec<f64>]) {
+    if embeddings.is_empty() {
+        return;
+    }
+    
+    let n = embeddings.len() as f64;
+    for dim in 0..embeddings[0].len() {
+        let mean = embeddings.iter().map(|e| e[dim]).sum::<f64>() / n;
+        let variance = embeddings.iter().map(|e| (e[dim] - mean).powi(2)).sum::<f64>() / n;
+        let std_dev = variance.sqrt();
+        
+        for embedding in embeddings.iter_mut() {
+            embedding[dim] -= mean;
+            if std_dev > 0.0 {
+                embedding[dim] /= std_dev;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn deterministic_mode_is_reproducible() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(11);
+        let data: Vec<Vec<f64>> = (0..200)
+            .map(|i| (0..8).map(|_| rng.gen::<f64>() + (i % 4) as f64).collect())
+            .collect();
+        let params = DimReductionParams { deterministic: true, seed: Some(5), ..Default::default() };
+        let run = || perform_dimension_reduction_with_params(&data, 2, Some(150), params);
+        
+        let first = run().unwrap();
+        let second = run().unwrap();
+        assert_eq!(first.original_indices, second.original_indices);
+        assert_eq!(first.embeddings, second.embeddings);
+    }
+    
+    #[test]
+    fn pca_rejects_ragged_rows() {
+        let data = vec![vec![0.0, 1.0], vec![2.0], vec![3.0, 4.0]];
+        let error = pca_reduction(&data, 1).unwrap_err();
+        assert_eq!(error.to_string(), "Row 1 has length 1, expected 2");
+    }
+}
@@ -0,0 +1,840 @@
+use anyhow::{anyhow, Result};
+use ndarray::Array2;
+use std::collections::HashMap;
+
+use crate::clustering::{hdbscan_mst, ClusteringResult};
+use crate::utils::{pairwise_distances, validate_row_lengths, DistanceMetric};
+
+/// One merge of a hierarchical clustering dendrogram
+///
+/// Follows the SciPy linkage-matrix convention: IDs below the number of points refer to
+/// single points, and merge step `i` creates the cluster with ID `n_points + i`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MergeStep {
+    /// ID of the first merged cluster
+    pub cluster_a: usize,
+    /// ID of the second merged cluster
+    pub cluster_b: usize,
+    /// Distance (height) at which the two clusters merge
+    pub distance: f64,
+    /// Number of points in the merged cluster
+    pub size: usize,
+}
+
+/// Computes the cophenetic distance matrix of a dendrogram
+///
+/// The cophenetic distance of two points is the height of the merge at which they first end
+/// up in the same cluster. This is the matrix used for the cophenetic correlation and for
+/// exporting dendrograms to other tools.
+///
+/// # Arguments
+/// * `merges` - The merge steps of the dendrogram
+/// * `n_points` - Number of points (leaves) in the dendrogram
+///
+/// # Returns
+/// * `Result<Array2<f64>>` - Symmetric N×N matrix with a zero diagonal, where pairs that are
+///   never merged get infinity, or error if a merge refers to an unknown cluster
+pub fn cophenetic_matrix(merges: &[MergeStep], n_points: usize) -> Result<Array2<f64>> {
+    let mut distances = Array2::from_elem((n_points, n_points), f64::INFINITY);
+    for i in 0..n_points {
+        distances[[i, i]] = 0.0;
+    }
+    
+    let mut members: Vec<Option<Vec<usize>>> = (0..n_points).map(|i| Some(vec![i])).collect();
+    for (step, merge) in merges.iter().enumerate() {
+        let mut take = |id: usize| -> Result<Vec<usize>> {
+            members
+                .get_mut(id)
+                .and_then(|m| m.take())
+                .ok_or_else(|| anyhow!("Merge step {} refers to unknown or already merged cluster {}", step, id))
+        };
+        let a = take(merge.cluster_a)?;
+        let b = take(merge.cluster_b)?;
+        
+        for &i in a.iter() {
+            for &j in b.iter() {
+                distances[[i, j]] = merge.distance;
+                distances[[j, i]] = merge.distance;
+            }
+        }
+        
+        let mut merged = a;
+        merged.extend(b);
+        members.push(Some(merged));
+    }
+    
+    Ok(distances)
+}
+
+/// Builds the single-linkage dendrogram of a minimum spanning tree
+///
+/// Merging the MST edges in order of increasing weight yields the single-linkage hierarchy;
+/// with the mutual reachability MST of `hdbscan_mst` this is HDBSCAN's cluster hierarchy.
+///
+/// # Arguments
+/// * `mst` - Edges `(a, b, weight)` of a spanning tree (or forest) over the points
+/// * `n_points` - Number of points
+///
+/// # Returns
+/// * `Result<Vec<MergeStep>>` - One merge per edge, in order of increasing distance, or error
+///   if an edge refers to an unknown point or closes a cycle
+pub fn single_linkage_tree(mst: &[(usize, usize, f64)], n_points: usize) -> Result<Vec<MergeStep>> {
+    let mut edges = mst.to_vec();
+    edges.sort_by(|a, b| a.2.total_cmp(&b.2));
+    
+    // Union-find over points; each root remembers the dendrogram ID and size of its component
+    let mut parent: Vec<usize> = (0..n_points).collect();
+    let mut cluster_id: Vec<usize> = (0..n_points).collect();
+    let mut size = vec![1usize; n_points];
+    fn find(parent: &mut [usize], mut x: usize) -> usize {
+        while parent[x] != x {
+            parent[x] = parent[parent[x]];
+            x = parent[x];
+        }
+        x
+    }
+    
+    let mut merges = Vec::with_capacity(edges.len());
+    for (a, b, distance) in edges {
+        if a >= n_points || b >= n_points {
+            return Err(anyhow!("Edge ({}, {}) refers to a point outside of 0..{}", a, b, n_points));
+        }
+        let (root_a, root_b) = (find(&mut parent, a), find(&mut parent, b));
+        if root_a == root_b {
+            return Err(anyhow!("Edge ({}, {}) closes a cycle; input must be a spanning tree", a, b));
+        }
+        
+        merges.push(MergeStep {
+            cluster_a: cluster_id[root_a],
+            cluster_b: cluster_id[root_b],
+            distance,
+            size: size[root_a] + size[root_b],
+        });
+        parent[root_b] = root_a;
+        size[root_a] += size[root_b];
+        cluster_id[root_a] = n_points + merges.len() - 1;
+    }
+    
+    Ok(merges)
+}
+
+/// Rule for the distance between two clusters in agglomerative clustering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Linkage {
+    /// Distance of the closest pair of points (tends to chain)
+    Single,
+    /// Distance of the farthest pair of points (compact clusters)
+    Complete,
+    /// Mean distance over all pairs of points (UPGMA)
+    #[default]
+    Average,
+    /// Merge the pair of clusters that least increases the total within-cluster variance.
+    /// Only meaningful on Euclidean distances
+    Ward,
+}
+
+/// Where to cut a dendrogram into flat clusters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DendrogramCut {
+    /// Keep exactly this many clusters (see `cut_dendrogram`)
+    NClusters(usize),
+    /// Apply every merge up to this height (see `cut_dendrogram_at_height`)
+    Height(f64),
+}
+
+/// Builds the agglomerative clustering dendrogram of a distance matrix
+///
+/// Uses the nearest-neighbor chain algorithm with Lance-Williams updates: O(N²) time and
+/// memory on top of the N×N input.
+///
+/// # Arguments
+/// * `distances` - Symmetric N×N distance matrix
+/// * `method` - The linkage rule
+///
+/// # Returns
+/// * `Result<Vec<MergeStep>>` - The N - 1 merges in order of increasing distance, or error if
+///   the matrix is not square
+pub fn linkage(distances: &Array2<f64>, method: Linkage) -> Result<Vec<MergeStep>> {
+    let (n, n_cols) = distances.dim();
+    if n != n_cols {
+        return Err(anyhow!("Distance matrix must be square, got {}x{}", n, n_cols));
+    }
+    
+    let mut d = distances.clone();
+    let mut size = vec![1usize; n];
+    let mut active = vec![true; n];
+    let mut chain: Vec<usize> = Vec::new();
+    // Merges as pairs of representative points; cluster `y` absorbs cluster `x`
+    let mut merges: Vec<(usize, usize, f64)> = Vec::with_capacity(n.saturating_sub(1));
+    
+    while merges.len() + 1 < n {
+        if chain.is_empty() {
+            chain.push(active.iter().position(|&a| a).expect("at least two active clusters"));
+        }
+        let (x, y) = loop {
+            let x = *chain.last().expect("non-empty chain");
+            let previous = chain.len().checked_sub(2).map(|i| chain[i]);
+            
+            // Nearest active neighbor of x, preferring the previous chain element on ties
+            let mut nearest = previous.map_or((usize::MAX, f64::INFINITY), |p| (p, d[[x, p]]));
+            for j in (0..n).filter(|&j| active[j] && j != x) {
+                if d[[x, j]] < nearest.1 || nearest.0 == usize::MAX {
+                    nearest = (j, d[[x, j]]);
+                }
+            }
+            
+            if Some(nearest.0) == previous {
+                chain.truncate(chain.len() - 2);
+                break (x, nearest.0);
+            }
+            chain.push(nearest.0);
+        };
+        
+        let d_xy = d[[x, y]];
+        for k in (0..n).filter(|&k| active[k] && k != x && k != y) {
+            let updated = match method {
+                Linkage::Single => d[[x, k]].min(d[[y, k]]),
+                Linkage::Complete => d[[x, k]].max(d[[y, k]]),
+                Linkage::Average => {
+                    (size[x] as f64 * d[[x, k]] + size[y] as f64 * d[[y, k]]) / (size[x] + size[y]) as f64
+                }
+                Linkage::Ward => {
+                    let (n_x, n_y, n_k) = (size[x] as f64, size[y] as f64, size[k] as f64);
+                    let squared = (n_x + n_k) * d[[x, k]].powi(2) + (n_y + n_k) * d[[y, k]].powi(2)
+                        - n_k * d_xy.powi(2);
+                    (squared / (n_x + n_y + n_k)).max(0.0).sqrt()
+                }
+            };
+            d[[y, k]] = updated;
+            d[[k, y]] = updated;
+        }
+        active[x] = false;
+        size[y] += size[x];
+        merges.push((x, y, d_xy));
+    }
+    
+    // The chain finds merges out of order; sorting and relabeling gives the SciPy dendrogram
+    single_linkage_tree(&merges, n)
+}
+
+/// Builds the agglomerative clustering dendrogram of a dataset
+///
+/// Computes the Euclidean distance matrix (8·N² bytes) and runs `linkage` on it. Keep the
+/// result to render the dendrogram or to cut it at several heights with `cut_dendrogram` and
+/// `cut_dendrogram_at_height` without recomputing it.
+///
+/// # Arguments
+/// * `data` - The data points
+/// * `method` - The linkage rule
+///
+/// # Returns
+/// * `Result<Vec<MergeStep>>` - The N - 1 merges in order of increasing distance, or error if
+///   the rows have different lengths
+pub fn agglomerative_dendrogram(data: &[Vec<f64>], method: Linkage) -> Result<Vec<MergeStep>> {
+    validate_row_lengths(data)?;
+    linkage(&pairwise_distances(data, DistanceMetric::Euclidean), method)
+}
+
+/// Builds a dendrogram over clusters from their centroids
+///
+/// Average-linkage agglomerative clustering of the centroids themselves, showing which flat
+/// clusters (e.g. of an over-segmented KMeans run) are related. Leaf `i` of the dendrogram is
+/// `centroids[i]`; pair with `centroids_from_result`, whose order is ascending cluster ID.
+///
+/// # Arguments
+/// * `centroids` - One centroid per cluster
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `Vec<MergeStep>` - The merges over the centroids, in order of increasing distance
+pub fn cluster_dendrogram(centroids: &[Vec<f64>], metric: DistanceMetric) -> Vec<MergeStep> {
+    let distances = pairwise_distances(centroids, metric);
+    linkage(&distances, Linkage::Average).expect("pairwise distance matrices are square")
+}
+
+/// Cuts a dendrogram into a fixed number of flat clusters
+///
+/// # Arguments
+/// * `merges` - The merge steps of the dendrogram, in order of increasing distance
+/// * `n_points` - Number of points (leaves) in the dendrogram
+/// * `n_clusters` - Number of clusters to keep
+///
+/// # Returns
+/// * `Result<Vec<usize>>` - Cluster label per point, numbered `0, 1, ...` in order of first
+///   appearance, or error if the dendrogram cannot produce `n_clusters` clusters
+pub fn cut_dendrogram(merges: &[MergeStep], n_points: usize, n_clusters: usize) -> Result<Vec<usize>> {
+    if n_clusters == 0 || n_clusters > n_points || n_points - n_clusters > merges.len() {
+        return Err(anyhow!(
+            "Cannot cut a dendrogram of {} points and {} merges into {} clusters",
+            n_points,
+            merges.len(),
+            n_clusters
+        ));
+    }
+    
+    // Union-find over dendrogram IDs: points and the clusters created by the applied merges
+    let n_merges = n_points - n_clusters;
+    let mut parent: Vec<usize> = (0..n_points + n_merges).collect();
+    fn find(parent: &mut [usize], mut x: usize) -> usize {
+        while parent[x] != x {
+            parent[x] = parent[parent[x]];
+            x = parent[x];
+        }
+        x
+    }
+    for (step, merge) in merges.iter().take(n_merges).enumerate() {
+        let new_id = n_points + step;
+        for child in [merge.cluster_a, merge.cluster_b] {
+            if child >= new_id {
+                return Err(anyhow!("Merge step {} refers to unknown cluster {}", step, child));
+            }
+            let root = find(&mut parent, child);
+            parent[root] = new_id;
+        }
+    }
+    
+    let mut labels_by_root: HashMap<usize, usize> = HashMap::new();
+    Ok((0..n_points)
+        .map(|i| {
+            let root = find(&mut parent, i);
+            let next = labels_by_root.len();
+            *labels_by_root.entry(root).or_insert(next)
+        })
+        .collect())
+}
+
+/// Cuts a dendrogram into flat clusters at a given height
+///
+/// Applies every merge whose distance is at most `height`, like SciPy's `fcluster` with the
+/// `distance` criterion, so points end up together exactly when their cophenetic distance
+/// is at most `height`.
+///
+/// # Arguments
+/// * `merges` - The merge steps of the dendrogram, in order of increasing distance
+/// * `n_points` - Number of points (leaves) in the dendrogram
+/// * `height` - The cut height
+///
+/// # Returns
+/// * `Result<Vec<usize>>` - Cluster label per point, numbered `0, 1, ...` in order of first
+///   appearance, or error if the dendrogram is malformed
+pub fn cut_dendrogram_at_height(merges: &[MergeStep], n_points: usize, height: f64) -> Result<Vec<usize>> {
+    let n_merges = merges.iter().take_while(|merge| merge.distance <= height).count();
+    cut_dendrogram(merges, n_points, n_points.saturating_sub(n_merges).max(1))
+}
+
+/// One edge of an HDBSCAN condensed tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CondensedTreeEdge {
+    /// ID of the parent cluster
+    pub parent: usize,
+    /// ID of the child: a point if below `n_points`, otherwise a cluster
+    pub child: usize,
+    /// Density level `1 / distance` at which the child leaves the parent
+    pub lambda: f64,
+    /// Number of points in the child
+    pub child_size: usize,
+}
+
+/// The condensed cluster tree of HDBSCAN
+///
+/// Follows the Python `hdbscan` library: IDs below `n_points` are points, the root cluster
+/// has ID `n_points` and further clusters are numbered in order of appearance. At each split
+/// of the single-linkage hierarchy, sides with at least `min_cluster_size` points become new
+/// clusters, while smaller sides "fall out" of the parent as individual points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CondensedTree {
+    /// All parent-child edges, parents before their children
+    pub edges: Vec<CondensedTreeEdge>,
+    /// Number of points in the tree
+    pub n_points: usize,
+}
+
+impl CondensedTree {
+    /// ID of the root cluster
+    pub fn root(&self) -> usize {
+        self.n_points
+    }
+    
+    /// Density level at which each cluster appears (0 for the root)
+    pub fn birth_lambdas(&self) -> HashMap<usize, f64> {
+        let mut births: HashMap<usize, f64> = HashMap::from([(self.root(), 0.0)]);
+        for edge in self.edges.iter().filter(|e| e.child >= self.n_points) {
+            births.insert(edge.child, edge.lambda);
+        }
+        births
+    }
+    
+    /// Stability of every cluster, the measure HDBSCAN maximizes when selecting clusters
+    ///
+    /// A cluster's stability is the sum over everything that leaves it (points and child
+    /// clusters) of `(lambda_leave - lambda_birth) * size`: how long, in density terms, its
+    /// points persist in it. Clusters containing duplicate points can have infinite stability,
+    /// and so do clusters born at `lambda = ∞`, which consist of duplicates only.
+    pub fn stability(&self) -> HashMap<usize, f64> {
+        let births = self.birth_lambdas();
+        let mut stability: HashMap<usize, f64> = births.keys().map(|&id| (id, 0.0)).collect();
+        for edge in self.edges.iter() {
+            let birth = births[&edge.parent];
+            // ∞ - ∞ would be NaN
+            let persistence = if birth.is_infinite() { f64::INFINITY } else { edge.lambda - birth };
+            *stability.entry(edge.parent).or_insert(0.0) += persistence * edge.child_size as f64;
+        }
+        stability
+    }
+    
+    /// Selects the flat clusters by excess of mass, as the Python `hdbscan` library does
+    ///
+    /// Walking up from the leaves, a cluster is selected when its stability is at least the
+    /// summed stability of the clusters selected below it; otherwise those stay selected and
+    /// their sum is carried upwards. The root is never selected, so a tree without splits
+    /// yields no clusters.
+    ///
+    /// # Returns
+    /// * `Vec<Vec<usize>>` - The points of every selected cluster, sorted, ordered by their
+    ///   smallest point; points outside all of them are outliers
+    pub fn select_clusters(&self) -> Vec<Vec<usize>> {
+        let stability = self.stability();
+        let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut falls_from = vec![self.root(); self.n_points];
+        for edge in &self.edges {
+            if edge.child < self.n_points {
+                falls_from[edge.child] = edge.parent;
+            } else {
+                children.entry(edge.parent).or_default().push(edge.child);
+            }
+        }
+        
+        // Children have higher IDs than their parents, so descending IDs visit them first
+        let mut ids: Vec<usize> = stability.keys().copied().filter(|&id| id != self.root()).collect();
+        ids.sort_unstable_by(|a, b| b.cmp(a));
+        let mut subtree_stability: HashMap<usize, f64> = HashMap::new();
+        let mut selected: HashMap<usize, bool> = HashMap::new();
+        for &id in &ids {
+            let below: f64 = children.get(&id).into_iter().flatten().map(|c| subtree_stability[c]).sum();
+            let keep = !children.contains_key(&id) || stability[&id] >= below;
+            selected.insert(id, keep);
+            subtree_stability.insert(id, if keep { stability[&id] } else { below });
+        }
+        
+        // A point belongs to the topmost selected cluster among the ancestors of its cluster
+        let mut parent_of: HashMap<usize, usize> = HashMap::new();
+        for (&parent, kids) in &children {
+            for &kid in kids {
+                parent_of.insert(kid, parent);
+            }
+        }
+        let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (point, &cluster) in falls_from.iter().enumerate() {
+            let chosen = std::iter::successors(Some(cluster), |c| parent_of.get(c).copied())
+                .filter(|c| selected.get(c).copied().unwrap_or(false))
+                .last();
+            if let Some(chosen) = chosen {
+                members.entry(chosen).or_default().push(point);
+            }
+        }
+        
+        let mut clusters: Vec<Vec<usize>> = members.into_values().collect();
+        clusters.sort_by_key(|points| points[0]);
+        clusters
+    }
+}
+
+/// Condenses a single-linkage dendrogram into an HDBSCAN condensed tree
+///
+/// # Arguments
+/// * `merges` - Single-linkage merge steps covering all points (see `single_linkage_tree`)
+/// * `n_points` - Number of points
+/// * `min_cluster_size` - Minimum number of points for a split side to count as a cluster (at least 2)
+///
+/// # Returns
+/// * `Result<CondensedTree>` - The condensed tree, or error if the dendrogram is not a single tree
+pub fn condense_tree(merges: &[MergeStep], n_points: usize, min_cluster_size: usize) -> Result<CondensedTree> {
+    if min_cluster_size < 2 {
+        return Err(anyhow!("min_cluster_size must be at least 2, got {}", min_cluster_size));
+    }
+    if n_points < 2 || merges.len() != n_points - 1 {
+        return Err(anyhow!(
+            "Expected {} merges for {} points, got {}",
+            n_points.saturating_sub(1),
+            n_points,
+            merges.len()
+        ));
+    }
+    
+    let size_of = |id: usize| if id < n_points { 1 } else { merges[id - n_points].size };
+    let leaves_of = |id: usize| -> Vec<usize> {
+        let mut leaves = Vec::new();
+        let mut stack = vec![id];
+        while let Some(node) = stack.pop() {
+            if node < n_points {
+                leaves.push(node);
+            } else {
+                let merge = &merges[node - n_points];
+                stack.push(merge.cluster_a);
+                stack.push(merge.cluster_b);
+            }
+        }
+        leaves
+    };
+    
+    let mut edges = Vec::new();
+    let mut next_label = n_points + 1;
+    // (dendrogram node, condensed cluster label it belongs to)
+    let mut stack = vec![(2 * n_points - 2, n_points)];
+    while let Some((node, label)) = stack.pop() {
+        let merge = &merges[node - n_points];
+        let lambda = if merge.distance > 0.0 { 1.0 / merge.distance } else { f64::INFINITY };
+        let children = [merge.cluster_a, merge.cluster_b];
+        let is_big = children.map(|c| size_of(c) >= min_cluster_size);
+        
+        if is_big[0] && is_big[1] {
+            for child in children {
+                edges.push(CondensedTreeEdge {
+                    parent: label,
+                    child: next_label,
+                    lambda,
+                    child_size: size_of(child),
+                });
+                stack.push((child, next_label));
+                next_label += 1;
+            }
+        } else {
+            for (child, big) in children.into_iter().zip(is_big) {
+                if big {
+                    // The cluster continues through its larger side under the same label
+                    stack.push((child, label));
+                } else {
+                    edges.extend(leaves_of(child).into_iter().map(|point| CondensedTreeEdge {
+                        parent: label,
+                        child: point,
+                        lambda,
+                        child_size: 1,
+                    }));
+                }
+            }
+        }
+    }
+    
+    Ok(CondensedTree { edges, n_points })
+}
+
+/// Computes the HDBSCAN condensed tree of a dataset
+///
+/// Built from the mutual reachability MST of `hdbscan_mst`. The flat clusters of
+/// `hdbscan_clustering` come from petal-clustering, whose cluster IDs do not match the IDs
+/// of this tree.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points
+/// * `min_cluster_size` - Minimum number of points to form a cluster (at least 2)
+/// * `min_samples` - Neighbors defining the core distance (default: `min_cluster_size`)
+///
+/// # Returns
+/// * `Result<CondensedTree>` - The condensed tree, or error
+pub fn hdbscan_condensed_tree(
+    data: &[Vec<f64>],
+    min_cluster_size: usize,
+    min_samples: Option<usize>,
+) -> Result<CondensedTree> {
+    let mst = hdbscan_mst(data, min_samples.unwrap_or(min_cluster_size))?;
+    let merges = single_linkage_tree(&mst, data.len())?;
+    condense_tree(&merges, data.len(), min_cluster_size)
+}
+
+/// Computes the stability of the clusters found by `hdbscan_clustering`
+///
+/// petal-clustering selects clusters by stability but does not expose it, so this rebuilds
+/// the condensed tree with `hdbscan_condensed_tree` and matches every cluster of `result`
+/// to the tree cluster whose points overlap it best (highest Jaccard similarity). Use the
+/// same `min_cluster_size` and `min_samples` as for the clustering, with the Euclidean
+/// metric. Stabilities are comparable within one tree only, and higher means the cluster
+/// persists over a wider density range. Costs O(N²), like `hdbscan_mst`.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The HDBSCAN clustering result
+/// * `min_cluster_size` - Minimum number of points to form a cluster (at least 2)
+/// * `min_samples` - Neighbors defining the core distance (default: `min_cluster_size`)
+///
+/// # Returns
+/// * `Result<HashMap<usize, f64>>` - Mapping of the cluster IDs of `result` to their
+///   stability, or error
+pub fn hdbscan_cluster_stability(
+    data: &[Vec<f64>],
+    result: &ClusteringResult,
+    min_cluster_size: usize,
+    min_samples: Option<usize>,
+) -> Result<HashMap<usize, f64>> {
+    if data.len() != result.assignments.len() {
+        return Err(anyhow!(
+            "Data has {} points but the clustering covers {}",
+            data.len(),
+            result.assignments.len()
+        ));
+    }
+    
+    let tree = hdbscan_condensed_tree(data, min_cluster_size, min_samples)?;
+    let stability = tree.stability();
+    
+    // Parent of every tree cluster, the cluster every point falls out of, and cluster sizes
+    let mut parent_of: HashMap<usize, usize> = HashMap::new();
+    let mut falls_from = vec![tree.root(); tree.n_points];
+    for edge in &tree.edges {
+        if edge.child < tree.n_points {
+            falls_from[edge.child] = edge.parent;
+        } else {
+            parent_of.insert(edge.child, edge.parent);
+        }
+    }
+    let ancestors = |cluster: usize| {
+        std::iter::successors(Some(cluster), |c| parent_of.get(c).copied())
+    };
+    let mut tree_sizes: HashMap<usize, usize> = HashMap::new();
+    for &cluster in &falls_from {
+        for ancestor in ancestors(cluster) {
+            *tree_sizes.entry(ancestor).or_insert(0) += 1;
+        }
+    }
+    
+    Ok(result
+        .clusters
+        .iter()
+        .filter(|(_, members)| !members.is_empty())
+        .map(|(&cluster_id, members)| {
+            let mut overlap: HashMap<usize, usize> = HashMap::new();
+            for &idx in members {
+                for ancestor in ancestors(falls_from[idx]) {
+                    *overlap.entry(ancestor).or_insert(0) += 1;
+                }
+            }
+            
+            let jaccard = |node: usize, shared: usize| {
+                shared as f64 / (tree_sizes[&node] + members.len() - shared) as f64
+            };
+            let (best, _) = overlap
+                .into_iter()
+                .map(|(node, shared)| (node, jaccard(node, shared)))
+                .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+                .expect("non-empty cluster overlaps the root");
+            (cluster_id, stability[&best])
+        })
+        .collect())
+}
+
+/// Moves the clusters whose stability is below a threshold to the outliers
+///
+/// # Arguments
+/// * `result` - The clustering result
+/// * `stability` - Stability per cluster ID (see `hdbscan_cluster_stability`); clusters
+///   without an entry are kept
+/// * `min_stability` - Clusters with a lower stability are dropped
+///
+/// # Returns
+/// * `ClusteringResult` - The clustering result without the unstable clusters
+pub fn drop_unstable_clusters(
+    result: &ClusteringResult,
+    stability: &HashMap<usize, f64>,
+    min_stability: f64,
+) -> ClusteringResult {
+    let (kept, dropped): (HashMap<usize, Vec<usize>>, HashMap<usize, Vec<usize>>) = result
+        .clusters
+        .clone()
+        .into_iter()
+        .partition(|(cluster_id, _)| stability.get(cluster_id).is_none_or(|&s| s >= min_stability));
+    
+    let mut outliers = result.outliers.clone();
+    outliers.extend(dropped.into_values().flatten());
+    outliers.sort_unstable();
+    
+    ClusteringResult::from_clusters(kept, outliers, result.assignments.len())
+}
+
+/// A cluster of the condensed tree in the nested form written by `condensed_tree_json`
+#[cfg(feature = "json")]
+#[derive(serde::Serialize)]
+struct JsonCluster {
+    id: usize,
+    lambda_birth: f64,
+    size: usize,
+    stability: f64,
+    children: Vec<JsonCluster>,
+}
+
+/// Serializes a condensed tree as nested JSON, e.g. for a D3 dendrogram
+///
+/// Every cluster is an object `{"id", "lambda_birth", "size", "stability", "children"}`
+/// where `children` holds its child clusters in the same form; individual points are
+/// omitted. The output is the root object. Infinite values (from duplicate points) are
+/// written as `null`. Requires the `json` feature.
+///
+/// # Arguments
+/// * `tree` - The condensed tree
+///
+/// # Returns
+/// * `String` - The JSON document
+#[cfg(feature = "json")]
+pub fn condensed_tree_json(tree: &CondensedTree) -> String {
+    let births = tree.birth_lambdas();
+    let stability = tree.stability();
+    let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut sizes: HashMap<usize, usize> = HashMap::from([(tree.root(), tree.n_points)]);
+    for edge in tree.edges.iter().filter(|e| e.child >= tree.n_points) {
+        children.entry(edge.parent).or_default().push(edge.child);
+        sizes.insert(edge.child, edge.child_size);
+    }
+    
+    fn build(
+        id: usize,
+        births: &HashMap<usize, f64>,
+        stability: &HashMap<usize, f64>,
+        sizes: &HashMap<usize, usize>,
+        children: &HashMap<usize, Vec<usize>>,
+    ) -> JsonCluster {
+        JsonCluster {
+            id,
+            lambda_birth: births[&id],
+            size: sizes[&id],
+            stability: stability[&id],
+            children: children
+                .get(&id)
+                .map(|ids| ids.iter().map(|&c| build(c, births, stability, sizes, children)).collect())
+                .unwrap_or_default(),
+        }
+    }
+    
+    let root = build(tree.root(), &births, &stability, &sizes, &children);
+    serde_json::to_string(&root).expect("condensed tree serialization cannot fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{euclidean_distance, mean_of_rows};
+    use rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256Plus;
+    
+    /// Naive O(N³) agglomeration computing every cluster distance from the point distances
+    fn naive_linkage(data: &[Vec<f64>], method: Linkage) -> Vec<MergeStep> {
+        let distances = pairwise_distances(data, DistanceMetric::Euclidean);
+        let cluster_distance = |a: &[usize], b: &[usize]| -> f64 {
+            let pairs = a.iter().flat_map(|&i| b.iter().map(move |&j| (i, j)));
+            match method {
+                Linkage::Single => pairs.map(|(i, j)| distances[[i, j]]).fold(f64::INFINITY, f64::min),
+                Linkage::Complete => pairs.map(|(i, j)| distances[[i, j]]).fold(0.0, f64::max),
+                Linkage::Average => {
+                    pairs.map(|(i, j)| distances[[i, j]]).sum::<f64>() / (a.len() * b.len()) as f64
+                }
+                Linkage::Ward => {
+                    // sqrt(2·|a|·|b| / (|a| + |b|)) times the distance between the centroids
+                    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+                    (2.0 * n_a * n_b / (n_a + n_b)).sqrt()
+                        * euclidean_distance(&mean_of_rows(data, a), &mean_of_rows(data, b))
+                }
+            }
+        };
+        
+        let mut clusters: Vec<(usize, Vec<usize>)> = (0..data.len()).map(|i| (i, vec![i])).collect();
+        let mut merges = Vec::new();
+        while clusters.len() > 1 {
+            let mut best = (0, 1, f64::INFINITY);
+            for i in 0..clusters.len() {
+                for j in i + 1..clusters.len() {
+                    let d = cluster_distance(&clusters[i].1, &clusters[j].1);
+                    if d < best.2 {
+                        best = (i, j, d);
+                    }
+                }
+            }
+            let (id_b, members_b) = clusters.remove(best.1);
+            let (id_a, mut members_a) = clusters.remove(best.0);
+            members_a.extend(members_b);
+            merges.push(MergeStep {
+                cluster_a: id_a,
+                cluster_b: id_b,
+                distance: best.2,
+                size: members_a.len(),
+            });
+            clusters.push((data.len() + merges.len() - 1, members_a));
+        }
+        merges
+    }
+    
+    #[test]
+    fn nn_chain_linkage_matches_naive_agglomeration() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(3);
+        for method in [Linkage::Single, Linkage::Complete, Linkage::Average, Linkage::Ward] {
+            for n_points in [2, 5, 12] {
+                let data: Vec<Vec<f64>> = (0..n_points)
+                    .map(|_| (0..3).map(|_| rng.gen_range(-10.0..10.0)).collect())
+                    .collect();
+                let merges = linkage(&pairwise_distances(&data, DistanceMetric::Euclidean), method).unwrap();
+                let expected = naive_linkage(&data, method);
+                
+                assert_eq!(merges.len(), n_points - 1);
+                assert!(merges.windows(2).all(|w| w[0].distance <= w[1].distance));
+                let actual = cophenetic_matrix(&merges, n_points).unwrap();
+                let expected = cophenetic_matrix(&expected, n_points).unwrap();
+                for (a, e) in actual.iter().zip(expected.iter()) {
+                    assert!((a - e).abs() < 1e-9, "{:?}: {} != {}", method, a, e);
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn height_cut_separates_distant_groups() {
+        let data = vec![vec![0.0], vec![1.0], vec![2.0], vec![20.0], vec![21.0]];
+        let merges = agglomerative_dendrogram(&data, Linkage::Ward).unwrap();
+        assert_eq!(cut_dendrogram_at_height(&merges, data.len(), 5.0).unwrap(), vec![0, 0, 0, 1, 1]);
+        assert_eq!(cut_dendrogram(&merges, data.len(), 2).unwrap(), vec![0, 0, 0, 1, 1]);
+        assert_eq!(cut_dendrogram_at_height(&merges, data.len(), 100.0).unwrap(), vec![0; 5]);
+        assert_eq!(cut_dendrogram_at_height(&merges, data.len(), 0.5).unwrap(), vec![0, 1, 2, 3, 4]);
+    }
+    
+    #[test]
+    fn condense_tree_splits_into_stable_clusters() {
+        // Points 0, 1, 2 and 10, 11, 12 on a line
+        let mst = vec![(0, 1, 1.0), (1, 2, 1.0), (2, 3, 8.0), (3, 4, 1.0), (4, 5, 1.0)];
+        let merges = single_linkage_tree(&mst, 6).unwrap();
+        let tree = condense_tree(&merges, 6, 2).unwrap();
+        
+        let clusters: Vec<_> = tree.edges.iter().filter(|e| e.child >= tree.n_points).collect();
+        assert_eq!(clusters.len(), 2);
+        assert!(clusters.iter().all(|e| e.parent == tree.root() && e.lambda == 0.125 && e.child_size == 3));
+        let parent_of: HashMap<usize, usize> =
+            tree.edges.iter().filter(|e| e.child < tree.n_points).map(|e| (e.child, e.parent)).collect();
+        assert_eq!(parent_of.len(), 6);
+        assert!((0..3).all(|p| parent_of[&p] == parent_of[&0]));
+        assert!((3..6).all(|p| parent_of[&p] == parent_of[&3]));
+        assert_ne!(parent_of[&0], parent_of[&3]);
+        assert!(tree.edges.iter().filter(|e| e.child < tree.n_points).all(|e| e.lambda == 1.0));
+        
+        let stability = tree.stability();
+        assert!((stability[&tree.root()] - 0.75).abs() < 1e-12);
+        assert!((stability[&parent_of[&0]] - 2.625).abs() < 1e-12);
+        assert_eq!(tree.select_clusters(), vec![vec![0, 1, 2], vec![3, 4, 5]]);
+        
+        // Neither side is big enough to be a cluster, so every point falls out of the root
+        let tree = condense_tree(&merges, 6, 4).unwrap();
+        assert_eq!(tree.edges.len(), 6);
+        assert!(tree.edges.iter().all(|e| e.parent == tree.root() && e.lambda == 0.125));
+        assert!(tree.select_clusters().is_empty());
+        
+        // Points 0, 1 and 2, 3 are duplicates at distance 0 of each other, so the clusters
+        // {0, 1} and {2, 3} are born at infinite density
+        let mst = vec![(0, 1, 0.0), (2, 3, 0.0), (1, 2, 0.0), (3, 4, 5.0), (4, 5, 1.0)];
+        let merges = single_linkage_tree(&mst, 6).unwrap();
+        let tree = condense_tree(&merges, 6, 2).unwrap();
+        let stability = tree.stability();
+        let born_at_infinity: Vec<usize> = tree
+            .edges
+            .iter()
+            .filter(|e| e.child >= tree.n_points && e.lambda == f64::INFINITY)
+            .map(|e| e.child)
+            .collect();
+        assert_eq!(born_at_infinity.len(), 2);
+        assert!(born_at_infinity.iter().all(|id| stability[id] == f64::INFINITY));
+        assert!(stability.values().all(|s| !s.is_nan()));
+        assert_eq!(tree.select_clusters(), vec![vec![0, 1, 2, 3], vec![4, 5]]);
+    }
+}
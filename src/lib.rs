@@ -1,7 +1,13 @@
 pub mod clustering;
 pub mod dimensionality_reduction;
+pub mod hierarchy;
+pub mod metrics;
+pub mod neighbors;
 pub mod utils;
 
 pub use clustering::*;
 pub use dimensionality_reduction::*;
-pub use utils::*; 
\ No newline at end of file
+pub use hierarchy::*;
+pub use metrics::*;
+pub use neighbors::*;
+pub use utils::*;
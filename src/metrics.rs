@@ -0,0 +1,1762 @@
+use anyhow::{anyhow, Result};
+use ndarray::Array2;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256Plus;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use crate::clustering::{
+    compute_centroids, fit_kmeans, group_by_cluster, kmeans_clustering, run_clustering, Algorithm,
+    ClusteringResult,
+};
+use crate::neighbors::{k_nearest_neighbors, local_outlier_factor};
+use crate::utils::{
+    euclidean_distance, global_mean, mean_of_rows, pairwise_distances, pairwise_distances_tiled, DistanceMetric,
+};
+
+/// Computes per-point cluster membership stability under bootstrap resampling
+///
+/// The data is clustered once to obtain reference labels. Each bootstrap round then
+/// resamples the points with replacement and reclusters them. For every sampled point,
+/// the round scores the fraction of its reference co-members (other sampled points that
+/// shared its reference cluster) that land in the same bootstrap cluster. Reference
+/// outliers instead score 1.0 when they are outliers again and 0.0 otherwise.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `algo` - The clustering algorithm to use for the reference and bootstrap runs
+/// * `n_boot` - Number of bootstrap rounds
+/// * `seed` - Random seed for the resampling
+///
+/// # Returns
+/// * `Result<Vec<f64>>` - Mean stability in [0, 1] per data point (0.0 for points that
+///   were never scored), or error
+pub fn bootstrap_stability(
+    data: &[Vec<f64>],
+    algo: Algorithm,
+    n_boot: usize,
+    seed: u64,
+) -> Result<Vec<f64>> {
+    let n = data.len();
+    if n == 0 {
+        return Err(anyhow!("Empty input data"));
+    }
+    if n_boot == 0 {
+        return Err(anyhow!("n_boot must be at least 1"));
+    }
+    
+    let reference = run_clustering(data, algo)?.labels();
+    let mut rng = Xoshiro256Plus::seed_from_u64(seed);
+    let mut score_sums = vec![0.0; n];
+    let mut score_counts = vec![0usize; n];
+    
+    for _ in 0..n_boot {
+        let sample: Vec<usize> = (0..n).map(|_| rng.gen_range(0..n)).collect();
+        let sample_data: Vec<Vec<f64>> = sample.iter().map(|&idx| data[idx].clone()).collect();
+        let boot_labels = run_clustering(&sample_data, algo)?.labels();
+        
+        // Bootstrap label of each distinct sampled point (first occurrence wins)
+        let mut sampled: HashMap<usize, Option<usize>> = HashMap::new();
+        for (pos, &idx) in sample.iter().enumerate() {
+            sampled.entry(idx).or_insert(boot_labels[pos]);
+        }
+        
+        // Count sampled members per (reference cluster, bootstrap label)
+        let mut present: HashMap<usize, usize> = HashMap::new();
+        let mut together: HashMap<(usize, usize), usize> = HashMap::new();
+        for (&idx, &boot_label) in sampled.iter() {
+            if let Some(ref_label) = reference[idx] {
+                *present.entry(ref_label).or_insert(0) += 1;
+                if let Some(boot_label) = boot_label {
+                    *together.entry((ref_label, boot_label)).or_insert(0) += 1;
+                }
+            }
+        }
+        
+        for (&idx, &boot_label) in sampled.iter() {
+            match reference[idx] {
+                Some(ref_label) => {
+                    let others = present[&ref_label] - 1;
+                    if others == 0 {
+                        continue;
+                    }
+                    let agreeing = boot_label
+                        .map(|label| together[&(ref_label, label)] - 1)
+                        .unwrap_or(0);
+                    score_sums[idx] += agreeing as f64 / others as f64;
+                }
+                None => {
+                    if boot_label.is_none() {
+                        score_sums[idx] += 1.0;
+                    }
+                }
+            }
+            score_counts[idx] += 1;
+        }
+    }
+    
+    Ok(score_sums
+        .iter()
+        .zip(score_counts.iter())
+        .map(|(&sum, &count)| if count > 0 { sum / count as f64 } else { 0.0 })
+        .collect())
+}
+
+/// Internal quality metrics of a clustering, as returned by `cluster_with_metrics`
+///
+/// Metrics are computed over non-outlier points only. Metrics that are undefined for the
+/// clustering (e.g. Davies-Bouldin with a single cluster) are `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClusterMetrics {
+    /// Mean silhouette coefficient (higher is better, range -1 to 1)
+    pub silhouette: Option<f64>,
+    /// Davies-Bouldin index (lower is better)
+    pub davies_bouldin: Option<f64>,
+    /// Calinski-Harabasz index (higher is better)
+    pub calinski_harabasz: Option<f64>,
+    /// Sum of squared distances of points to their cluster centroid
+    pub inertia: f64,
+    /// Number of clusters, excluding outliers
+    pub n_clusters: usize,
+}
+
+/// Clusters a dataset and computes its internal quality metrics in one call
+///
+/// The silhouette and Davies-Bouldin scores use the distance metric of `algo`; the
+/// Calinski-Harabasz index and inertia are Euclidean by definition.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `algo` - The clustering algorithm to use
+///
+/// # Returns
+/// * `Result<(ClusteringResult, ClusterMetrics)>` - The clustering result and its metrics, or error
+pub fn cluster_with_metrics(
+    data: &[Vec<f64>],
+    algo: Algorithm,
+) -> Result<(ClusteringResult, ClusterMetrics)> {
+    let result = run_clustering(data, algo)?;
+    
+    // Restrict the evaluation to clustered (non-outlier) points
+    let mut clustered_data = Vec::new();
+    let mut clustered_assignments = Vec::new();
+    for (idx, label) in result.labels().into_iter().enumerate() {
+        if let Some(cluster_id) = label {
+            clustered_data.push(data[idx].clone());
+            clustered_assignments.push(cluster_id);
+        }
+    }
+    
+    let metrics = if clustered_data.is_empty() {
+        ClusterMetrics {
+            silhouette: None,
+            davies_bouldin: None,
+            calinski_harabasz: None,
+            inertia: 0.0,
+            n_clusters: 0,
+        }
+    } else {
+        let metric = algo.metric();
+        ClusterMetrics {
+            silhouette: silhouette_score(&clustered_data, &clustered_assignments, metric).ok(),
+            davies_bouldin: davies_bouldin_score(&clustered_data, &clustered_assignments, metric).ok(),
+            calinski_harabasz: calinski_harabasz_score(&clustered_data, &clustered_assignments).ok(),
+            inertia: inertia(&clustered_data, &clustered_assignments)?,
+            n_clusters: result.clusters.len(),
+        }
+    };
+    
+    Ok((result, metrics))
+}
+
+/// Selects the number of KMeans clusters that maximizes the mean silhouette coefficient
+///
+/// Fits `kmeans_clustering` for every `k` in `k_range` and scores each fit with
+/// `silhouette_score`. Ties go to the smallest `k`.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `k_range` - Candidate cluster counts (must be non-empty and start at 2 or more, since
+///   the silhouette is undefined for a single cluster)
+/// * `seed` - Random seed for KMeans initialization (default: 42)
+///
+/// # Returns
+/// * `Result<(usize, ClusteringResult)>` - The best `k` and its clustering result, or error
+pub fn kmeans_best_k_silhouette(
+    data: &[Vec<f64>],
+    k_range: Range<usize>,
+    seed: Option<u64>,
+) -> Result<(usize, ClusteringResult)> {
+    if k_range.is_empty() {
+        return Err(anyhow!("k_range is empty"));
+    }
+    if k_range.start < 2 {
+        return Err(anyhow!("k_range must start at 2 or more, got {}", k_range.start));
+    }
+    
+    let mut best: Option<(usize, f64, ClusteringResult)> = None;
+    for k in k_range {
+        let result = kmeans_clustering(data, k, None, None, seed)?;
+        let score = silhouette_score(data, &result.assignments, DistanceMetric::Euclidean)?;
+        if !matches!(best, Some((_, best_score, _)) if score <= best_score) {
+            best = Some((k, score, result));
+        }
+    }
+    
+    let (k, _, result) = best.expect("k_range is non-empty");
+    Ok((k, result))
+}
+
+/// Estimates the number of clusters with Tibshirani & Walther's prediction strength
+///
+/// The points are shuffled and split into two halves, and each half is clustered with
+/// `fit_kmeans`. The centroids of the first half then label the second half; for every
+/// cluster of the second half's own clustering, we measure the fraction of its point pairs
+/// that those centroids also put together. The prediction strength of `k` is the smallest
+/// such fraction, and the estimate is the largest `k` whose strength is at least 0.8.
+/// Clusters with fewer than two points carry no pairs and are skipped; if no cluster has a
+/// pair, nothing was measured and the strength is 0, so that `k` is never chosen.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `k_range` - Candidate cluster counts (must be non-empty and start at 1 or more)
+/// * `seed` - Random seed for the split and for KMeans initialization
+///
+/// # Returns
+/// * `Result<(usize, Vec<f64>)>` - The estimated `k` (1 if no candidate reaches 0.8) and the
+///   prediction strength of each `k` in `k_range`, or error
+pub fn prediction_strength(data: &[Vec<f64>], k_range: Range<usize>, seed: u64) -> Result<(usize, Vec<f64>)> {
+    const THRESHOLD: f64 = 0.8;
+    if k_range.is_empty() {
+        return Err(anyhow!("k_range is empty"));
+    }
+    if k_range.start == 0 {
+        return Err(anyhow!("k_range must start at 1 or more"));
+    }
+    if k_range.end - 1 > data.len() / 2 {
+        return Err(anyhow!(
+            "k_range goes up to {} but each half of the data has at most {} points",
+            k_range.end - 1,
+            data.len() / 2
+        ));
+    }
+    
+    let mut rng = Xoshiro256Plus::seed_from_u64(seed);
+    let mut indices: Vec<usize> = (0..data.len()).collect();
+    indices.shuffle(&mut rng);
+    let (train_idx, test_idx) = indices.split_at(data.len() / 2);
+    let train: Vec<Vec<f64>> = train_idx.iter().map(|&i| data[i].clone()).collect();
+    let test: Vec<Vec<f64>> = test_idx.iter().map(|&i| data[i].clone()).collect();
+    
+    let mut best_k = 1;
+    let mut strengths = Vec::with_capacity(k_range.len());
+    for k in k_range {
+        let train_model = fit_kmeans(&train, k, None, None, Some(seed))?;
+        let test_model = fit_kmeans(&test, k, None, None, Some(seed))?;
+        let predicted = train_model.predict(&test)?;
+        let strength = min_pair_agreement(test_model.clustering.clusters.values(), &predicted);
+        
+        if strength >= THRESHOLD {
+            best_k = k;
+        }
+        strengths.push(strength);
+    }
+    
+    Ok((best_k, strengths))
+}
+
+/// Smallest fraction of a cluster's point pairs that `predicted` also puts together, over
+/// the clusters with at least two points; 0 if there are none
+fn min_pair_agreement<'a>(clusters: impl Iterator<Item = &'a Vec<usize>>, predicted: &[usize]) -> f64 {
+    clusters
+        .filter(|members| members.len() >= 2)
+        .map(|members| {
+            let n = members.len();
+            let mut co_members = 0usize;
+            for (a, &i) in members.iter().enumerate() {
+                co_members += members[a + 1..].iter().filter(|&&j| predicted[i] == predicted[j]).count();
+            }
+            co_members as f64 / (n * (n - 1) / 2) as f64
+        })
+        .reduce(f64::min)
+        .unwrap_or(0.0)
+}
+
+/// Computes the mean silhouette coefficient of a clustering
+///
+/// For each point, `a` is the mean distance to the other members of its cluster and `b` the
+/// smallest mean distance to the members of another cluster; its silhouette is
+/// `(b - a) / max(a, b)`. Points in singleton clusters, or in a clustering with a single
+/// cluster, score 0. Evaluate with the metric the clustering used: e.g. the Euclidean
+/// silhouette of a cosine clustering is misleadingly low.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `assignments` - Cluster assignment of each data point
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `Result<f64>` - Mean silhouette coefficient (-1 to 1, higher is better) or error
+pub fn silhouette_score(data: &[Vec<f64>], assignments: &[usize], metric: DistanceMetric) -> Result<f64> {
+    let clusters = indices_by_cluster(data, assignments)?;
+    Ok(mean_silhouette(&clusters, assignments, |i, j| metric.distance(&data[i], &data[j])))
+}
+
+/// Computes the silhouette coefficient of every point
+///
+/// The per-point values that `silhouette_score` averages, with the same definition, e.g. to
+/// find poorly clustered points (those with negative values sit closer to another cluster
+/// than to their own). Points in singleton clusters, or in a clustering with a single
+/// cluster, score 0.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `assignments` - Cluster assignment of each data point
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `Result<Vec<f64>>` - Silhouette coefficient (-1 to 1) per point, or error
+pub fn silhouette_samples(
+    data: &[Vec<f64>],
+    assignments: &[usize],
+    metric: DistanceMetric,
+) -> Result<Vec<f64>> {
+    let clusters = indices_by_cluster(data, assignments)?;
+    Ok(silhouette_values(&clusters, assignments, |i, j| metric.distance(&data[i], &data[j])))
+}
+
+/// Computes the mean silhouette coefficient from a precomputed distance matrix
+///
+/// Same definition as `silhouette_score`, but reads distances from `distances` instead of
+/// recomputing them, so it works with any metric and avoids redundant work in pipelines that
+/// already built the matrix.
+///
+/// # Arguments
+/// * `distances` - Symmetric N×N distance matrix
+/// * `assignments` - Cluster assignment of each data point
+///
+/// # Returns
+/// * `Result<f64>` - Mean silhouette coefficient (-1 to 1, higher is better) or error
+pub fn silhouette_from_distances(distances: &Array2<f64>, assignments: &[usize]) -> Result<f64> {
+    let (n_rows, n_cols) = distances.dim();
+    if n_rows != n_cols {
+        return Err(anyhow!("Distance matrix must be square, got {}x{}", n_rows, n_cols));
+    }
+    if n_rows != assignments.len() {
+        return Err(anyhow!(
+            "Distance matrix covers {} points but {} assignments were given",
+            n_rows,
+            assignments.len()
+        ));
+    }
+    if n_rows == 0 {
+        return Err(anyhow!("Empty input data"));
+    }
+    
+    let indices: Vec<usize> = (0..n_rows).collect();
+    let clusters = group_by_cluster(assignments, &indices);
+    Ok(mean_silhouette(&clusters, assignments, |i, j| distances[[i, j]]))
+}
+
+/// Computes the mean silhouette coefficient without materializing the distance matrix
+///
+/// Same result as `silhouette_score`, but distances are streamed with
+/// `pairwise_distances_tiled`, so memory use is 8·`tile_size`² bytes for the current tile plus
+/// one running sum per point and cluster, instead of 8·N² bytes.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `assignments` - Cluster assignment of each data point
+/// * `tile_size` - Maximum number of rows and columns per distance tile
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `Result<f64>` - Mean silhouette coefficient (-1 to 1, higher is better) or error
+pub fn silhouette_score_tiled(
+    data: &[Vec<f64>],
+    assignments: &[usize],
+    tile_size: usize,
+    metric: DistanceMetric,
+) -> Result<f64> {
+    let clusters = indices_by_cluster(data, assignments)?;
+    let mut ids: Vec<usize> = clusters.keys().cloned().collect();
+    ids.sort_unstable();
+    let position: HashMap<usize, usize> = ids.iter().enumerate().map(|(pos, &id)| (id, pos)).collect();
+    
+    // Sum of distances from every point to the members of every cluster
+    let mut sums = vec![vec![0.0; ids.len()]; data.len()];
+    for tile in pairwise_distances_tiled(data, metric, tile_size) {
+        for ((i, j), &d) in tile.distances.indexed_iter() {
+            sums[tile.row_start + i][position[&assignments[tile.col_start + j]]] += d;
+        }
+    }
+    
+    let mut total = 0.0;
+    for (idx, &cluster_id) in assignments.iter().enumerate() {
+        let own_size = clusters[&cluster_id].len();
+        if own_size < 2 || ids.len() < 2 {
+            continue;
+        }
+        
+        let own = position[&cluster_id];
+        let a = sums[idx][own] / (own_size - 1) as f64;
+        let b = ids
+            .iter()
+            .enumerate()
+            .filter(|&(pos, _)| pos != own)
+            .map(|(pos, id)| sums[idx][pos] / clusters[id].len() as f64)
+            .fold(f64::INFINITY, f64::min);
+        
+        let denominator = a.max(b);
+        if denominator > 0.0 {
+            total += (b - a) / denominator;
+        }
+    }
+    
+    Ok(total / assignments.len() as f64)
+}
+
+/// Mean silhouette coefficient over all points, given the cluster members and a distance
+/// function between point indices
+fn mean_silhouette(
+    clusters: &HashMap<usize, Vec<usize>>,
+    assignments: &[usize],
+    distance: impl Fn(usize, usize) -> f64,
+) -> f64 {
+    silhouette_values(clusters, assignments, distance).iter().sum::<f64>() / assignments.len() as f64
+}
+
+/// Silhouette coefficient of every point, given the cluster members and a distance function
+/// between point indices
+fn silhouette_values(
+    clusters: &HashMap<usize, Vec<usize>>,
+    assignments: &[usize],
+    distance: impl Fn(usize, usize) -> f64,
+) -> Vec<f64> {
+    assignments
+        .iter()
+        .enumerate()
+        .map(|(idx, &cluster_id)| {
+            let own = &clusters[&cluster_id];
+            if own.len() < 2 || clusters.len() < 2 {
+                return 0.0;
+            }
+            
+            let a = own
+                .iter()
+                .filter(|&&other| other != idx)
+                .map(|&other| distance(idx, other))
+                .sum::<f64>()
+                / (own.len() - 1) as f64;
+            let b = clusters
+                .iter()
+                .filter(|(&other_id, _)| other_id != cluster_id)
+                .map(|(_, members)| {
+                    members.iter().map(|&other| distance(idx, other)).sum::<f64>() / members.len() as f64
+                })
+                .fold(f64::INFINITY, f64::min);
+            
+            let denominator = a.max(b);
+            if denominator > 0.0 {
+                (b - a) / denominator
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Computes the Davies-Bouldin index of a clustering
+///
+/// The average, over clusters, of the worst-case ratio of within-cluster scatter to
+/// between-centroid distance. Scatter and separation are measured with `metric` around the
+/// mean of each cluster.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `assignments` - Cluster assignment of each data point
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `Result<f64>` - Davies-Bouldin index (lower is better) or error if there are fewer than 2 clusters
+pub fn davies_bouldin_score(data: &[Vec<f64>], assignments: &[usize], metric: DistanceMetric) -> Result<f64> {
+    let clusters = indices_by_cluster(data, assignments)?;
+    if clusters.len() < 2 {
+        return Err(anyhow!("Davies-Bouldin index requires at least 2 clusters"));
+    }
+    
+    let centroids: Vec<Vec<f64>> = clusters.values().map(|members| mean_of_rows(data, members)).collect();
+    let scatters: Vec<f64> = clusters
+        .values()
+        .zip(centroids.iter())
+        .map(|(members, centroid)| {
+            members
+                .iter()
+                .map(|&idx| metric.distance(&data[idx], centroid))
+                .sum::<f64>()
+                / members.len() as f64
+        })
+        .collect();
+    
+    let mut total = 0.0;
+    for i in 0..centroids.len() {
+        let worst = (0..centroids.len())
+            .filter(|&j| j != i)
+            .map(|j| {
+                let separation = metric.distance(&centroids[i], &centroids[j]);
+                if separation > 0.0 {
+                    (scatters[i] + scatters[j]) / separation
+                } else {
+                    0.0
+                }
+            })
+            .fold(0.0, f64::max);
+        total += worst;
+    }
+    
+    Ok(total / centroids.len() as f64)
+}
+
+/// Computes the Calinski-Harabasz index (variance ratio criterion) of a clustering
+///
+/// The ratio of between-cluster dispersion to within-cluster dispersion, each normalized
+/// by its degrees of freedom.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `assignments` - Cluster assignment of each data point
+///
+/// # Returns
+/// * `Result<f64>` - Calinski-Harabasz index (higher is better) or error if the number of
+///   clusters is not between 2 and the number of points minus 1
+pub fn calinski_harabasz_score(data: &[Vec<f64>], assignments: &[usize]) -> Result<f64> {
+    let clusters = indices_by_cluster(data, assignments)?;
+    let n = data.len();
+    let k = clusters.len();
+    if k < 2 || k >= n {
+        return Err(anyhow!(
+            "Calinski-Harabasz index requires between 2 and {} clusters, got {}",
+            n - 1,
+            k
+        ));
+    }
+    
+    let mean = global_mean(data);
+    let mut between = 0.0;
+    let mut within = 0.0;
+    for members in clusters.values() {
+        let centroid = mean_of_rows(data, members);
+        between += members.len() as f64 * euclidean_distance(&centroid, &mean).powi(2);
+        within += members
+            .iter()
+            .map(|&idx| euclidean_distance(&data[idx], &centroid).powi(2))
+            .sum::<f64>();
+    }
+    
+    if within == 0.0 {
+        return Ok(1.0);
+    }
+    
+    Ok((between / (k - 1) as f64) / (within / (n - k) as f64))
+}
+
+/// Computes the inertia of a clustering
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `assignments` - Cluster assignment of each data point
+///
+/// # Returns
+/// * `Result<f64>` - Sum of squared distances of points to their cluster centroid, or error
+pub fn inertia(data: &[Vec<f64>], assignments: &[usize]) -> Result<f64> {
+    let clusters = indices_by_cluster(data, assignments)?;
+    
+    Ok(clusters
+        .values()
+        .map(|members| {
+            let centroid = mean_of_rows(data, members);
+            members
+                .iter()
+                .map(|&idx| euclidean_distance(&data[idx], &centroid).powi(2))
+                .sum::<f64>()
+        })
+        .sum())
+}
+
+/// Validates metric inputs and groups point indices by cluster assignment
+fn indices_by_cluster(data: &[Vec<f64>], assignments: &[usize]) -> Result<HashMap<usize, Vec<usize>>> {
+    if data.is_empty() {
+        return Err(anyhow!("Empty input data"));
+    }
+    if data.len() != assignments.len() {
+        return Err(anyhow!(
+            "Data has {} points but {} assignments were given",
+            data.len(),
+            assignments.len()
+        ));
+    }
+    
+    let indices: Vec<usize> = (0..data.len()).collect();
+    Ok(group_by_cluster(assignments, &indices))
+}
+
+/// Finds the data points whose cluster changed between two clustering runs
+///
+/// Cluster IDs are arbitrary, so with `relabel` the clusters of `b` are first matched to the
+/// clusters of `a` by greedily pairing the clusters that share the most points. Points that
+/// are outliers in both runs are considered unchanged.
+///
+/// # Arguments
+/// * `a` - The first clustering result
+/// * `b` - The second clustering result, over the same data points
+/// * `relabel` - Whether to match cluster IDs between the runs before comparing
+///
+/// # Returns
+/// * `Result<Vec<usize>>` - Sorted indices of the points whose cluster changed, or error
+pub fn assignment_diff(a: &ClusteringResult, b: &ClusteringResult, relabel: bool) -> Result<Vec<usize>> {
+    if a.assignments.len() != b.assignments.len() {
+        return Err(anyhow!(
+            "Clustering results cover different numbers of points: {} and {}",
+            a.assignments.len(),
+            b.assignments.len()
+        ));
+    }
+    
+    let labels_a = a.labels();
+    let mut labels_b = b.labels();
+    
+    if relabel {
+        let mut overlap: HashMap<(usize, usize), usize> = HashMap::new();
+        for (la, lb) in labels_a.iter().zip(labels_b.iter()) {
+            if let (Some(la), Some(lb)) = (la, lb) {
+                *overlap.entry((*lb, *la)).or_insert(0) += 1;
+            }
+        }
+        
+        // Largest overlaps first, ties broken by cluster IDs for determinism
+        let mut pairs: Vec<((usize, usize), usize)> = overlap.into_iter().collect();
+        pairs.sort_by(|x, y| y.1.cmp(&x.1).then(x.0.cmp(&y.0)));
+        
+        let mut mapping: HashMap<usize, usize> = HashMap::new();
+        let mut used: HashSet<usize> = HashSet::new();
+        for ((lb, la), _) in pairs {
+            if !mapping.contains_key(&lb) && !used.contains(&la) {
+                mapping.insert(lb, la);
+                used.insert(la);
+            }
+        }
+        
+        // Unmatched clusters of `b` get IDs that cannot collide with any cluster of `a`
+        let mut next_unmatched = labels_a.iter().flatten().max().map_or(0, |&m| m + 1);
+        for lb in b.clusters.keys() {
+            mapping.entry(*lb).or_insert_with(|| {
+                next_unmatched += 1;
+                next_unmatched - 1
+            });
+        }
+        
+        for label in labels_b.iter_mut() {
+            *label = label.map(|lb| mapping[&lb]);
+        }
+    }
+    
+    Ok(labels_a
+        .iter()
+        .zip(labels_b.iter())
+        .enumerate()
+        .filter(|(_, (la, lb))| la != lb)
+        .map(|(idx, _)| idx)
+        .collect())
+}
+
+/// Computes the Shannon entropy of a categorical attribute within each cluster
+///
+/// Shows whether clusters are dominated by one attribute value (e.g. a single data source)
+/// or mix several. The entropy is in bits: 0.0 means every member has the same value, and
+/// `log2(m)` means the members are spread evenly over `m` values. Outliers are not part of
+/// any cluster and are ignored.
+///
+/// # Arguments
+/// * `result` - The clustering result
+/// * `attribute` - Categorical attribute value of each data point
+///
+/// # Returns
+/// * `Result<HashMap<usize, f64>>` - Mapping of cluster IDs to attribute entropy, or error if
+///   `attribute` does not have one value per data point
+pub fn cluster_attribute_entropy(
+    result: &ClusteringResult,
+    attribute: &[usize],
+) -> Result<HashMap<usize, f64>> {
+    if attribute.len() != result.assignments.len() {
+        return Err(anyhow!(
+            "Attribute has {} values but the clustering has {} points",
+            attribute.len(),
+            result.assignments.len()
+        ));
+    }
+    
+    Ok(result
+        .clusters
+        .iter()
+        .map(|(&cluster_id, members)| {
+            let mut counts: HashMap<usize, usize> = HashMap::new();
+            for &idx in members {
+                *counts.entry(attribute[idx]).or_insert(0) += 1;
+            }
+            let entropy: f64 = counts
+                .values()
+                .map(|&count| {
+                    let p = count as f64 / members.len() as f64;
+                    -p * p.log2()
+                })
+                .sum();
+            (cluster_id, entropy.abs())
+        })
+        .collect())
+}
+
+/// Estimates the density of every cluster
+///
+/// A cluster's density is the mean, over its members, of the inverse distance to the
+/// nearest other member of the same cluster. Unlike count-per-bounding-box-volume, this stays
+/// finite and comparable in high dimensions, but it is scale dependent (only compare values
+/// computed on the same data) and reflects local rather than global tightness. Singleton
+/// clusters get density 0; members with an exact duplicate are skipped, and a cluster made
+/// only of duplicates gets infinite density. Costs O(size²) distance computations per cluster.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+///
+/// # Returns
+/// * `HashMap<usize, f64>` - Mapping of cluster IDs to their density (higher is denser)
+pub fn cluster_densities(data: &[Vec<f64>], result: &ClusteringResult) -> HashMap<usize, f64> {
+    result
+        .clusters
+        .iter()
+        .map(|(&cluster_id, members)| {
+            if members.len() < 2 {
+                return (cluster_id, 0.0);
+            }
+            
+            let inverse_distances: Vec<f64> = members
+                .iter()
+                .map(|&idx| {
+                    members
+                        .iter()
+                        .filter(|&&other| other != idx)
+                        .map(|&other| euclidean_distance(&data[idx], &data[other]))
+                        .fold(f64::INFINITY, f64::min)
+                })
+                .filter(|&d| d > 0.0)
+                .map(|d| 1.0 / d)
+                .collect();
+            
+            let density = if inverse_distances.is_empty() {
+                f64::INFINITY
+            } else {
+                inverse_distances.iter().sum::<f64>() / inverse_distances.len() as f64
+            };
+            (cluster_id, density)
+        })
+        .collect()
+}
+
+/// Computes percentiles of the member-to-centroid distances of every cluster
+///
+/// A high percentile (e.g. the 99th) makes a per-cluster novelty threshold that, unlike the
+/// cluster diameter, a single far-out member cannot inflate. Percentiles use linear
+/// interpolation between the sorted distances (NumPy's default). Outliers are ignored.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+/// * `percentiles` - The percentiles to compute, each in `[0, 100]`
+///
+/// # Returns
+/// * `Result<HashMap<usize, Vec<f64>>>` - Mapping of cluster IDs to their Euclidean distance
+///   percentiles, in the order requested, or error if a percentile is out of range
+pub fn cluster_radius_percentiles(
+    data: &[Vec<f64>],
+    result: &ClusteringResult,
+    percentiles: &[f64],
+) -> Result<HashMap<usize, Vec<f64>>> {
+    if let Some(p) = percentiles.iter().find(|p| !(0.0..=100.0).contains(*p)) {
+        return Err(anyhow!("Percentiles must be in [0, 100], got {}", p));
+    }
+    
+    let centroids = compute_centroids(data, result);
+    Ok(centroids
+        .iter()
+        .map(|(&cluster_id, centroid)| {
+            let mut distances: Vec<f64> = result.clusters[&cluster_id]
+                .iter()
+                .map(|&idx| euclidean_distance(&data[idx], centroid))
+                .collect();
+            distances.sort_by(|a, b| a.total_cmp(b));
+            
+            let values = percentiles
+                .iter()
+                .map(|&p| {
+                    let rank = p / 100.0 * (distances.len() - 1) as f64;
+                    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+                    distances[lower] + (rank - lower as f64) * (distances[upper] - distances[lower])
+                })
+                .collect();
+            (cluster_id, values)
+        })
+        .collect())
+}
+
+/// Computes the sum of pairwise distances among the members of every cluster
+///
+/// Each unordered pair of members is counted once. Unlike centroid-based scatter this works
+/// with any metric, and it is the within-cluster term of pair-based indices such as the
+/// C-index. Costs O(size²) distance computations per cluster. Outliers are ignored.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `HashMap<usize, f64>` - Mapping of cluster IDs to their intra-cluster distance sum
+pub fn intra_cluster_distance_sum(
+    data: &[Vec<f64>],
+    result: &ClusteringResult,
+    metric: DistanceMetric,
+) -> HashMap<usize, f64> {
+    result
+        .clusters
+        .iter()
+        .map(|(&cluster_id, members)| {
+            let sum = members
+                .iter()
+                .enumerate()
+                .flat_map(|(pos, &a)| members[pos + 1..].iter().map(move |&b| (a, b)))
+                .map(|(a, b)| metric.distance(&data[a], &data[b]))
+                .sum();
+            (cluster_id, sum)
+        })
+        .collect()
+}
+
+/// Computes the C-index of a clustering
+///
+/// With `S` the sum of the `W` within-cluster pairwise distances, and `S_min` / `S_max` the
+/// sums of the `W` smallest / largest pairwise distances overall, the C-index is
+/// `(S - S_min) / (S_max - S_min)`. It compares pairs rather than centroids, so it suits
+/// non-globular clusters. Outliers are ignored. Costs O(N²) distance computations and
+/// O(N² log N) time; see `c_index_from_distances` to reuse a distance matrix.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `Result<f64>` - The C-index in [0, 1] (lower is better), or error if no cluster has two
+///   members or all pairwise distances are equal
+pub fn c_index(data: &[Vec<f64>], result: &ClusteringResult, metric: DistanceMetric) -> Result<f64> {
+    c_index_from_distances(&pairwise_distances(data, metric), result)
+}
+
+/// Computes the C-index of a clustering from a precomputed distance matrix
+///
+/// Same definition as `c_index`, but reads distances from `distances` instead of
+/// recomputing them.
+///
+/// # Arguments
+/// * `distances` - Symmetric N×N distance matrix
+/// * `result` - The clustering result
+///
+/// # Returns
+/// * `Result<f64>` - The C-index in [0, 1] (lower is better), or error
+pub fn c_index_from_distances(distances: &Array2<f64>, result: &ClusteringResult) -> Result<f64> {
+    let (n_rows, n_cols) = distances.dim();
+    if n_rows != n_cols || n_rows != result.assignments.len() {
+        return Err(anyhow!(
+            "Distance matrix of shape {}x{} does not match {} assignments",
+            n_rows,
+            n_cols,
+            result.assignments.len()
+        ));
+    }
+    
+    let labels = result.labels();
+    let clustered: Vec<usize> = (0..n_rows).filter(|&i| labels[i].is_some()).collect();
+    
+    let mut all_pairs = Vec::with_capacity(clustered.len() * clustered.len().saturating_sub(1) / 2);
+    let mut within_sum = 0.0;
+    let mut n_within = 0;
+    for (pos, &i) in clustered.iter().enumerate() {
+        for &j in &clustered[pos + 1..] {
+            let distance = distances[[i, j]];
+            all_pairs.push(distance);
+            if labels[i] == labels[j] {
+                within_sum += distance;
+                n_within += 1;
+            }
+        }
+    }
+    if n_within == 0 {
+        return Err(anyhow!("The C-index needs at least one cluster with two members"));
+    }
+    
+    all_pairs.sort_by(|a, b| a.total_cmp(b));
+    let min_sum: f64 = all_pairs[..n_within].iter().sum();
+    let max_sum: f64 = all_pairs[all_pairs.len() - n_within..].iter().sum();
+    if max_sum <= min_sum {
+        return Err(anyhow!("The C-index is undefined when all pairwise distances are equal"));
+    }
+    
+    Ok((within_sum - min_sum) / (max_sum - min_sum))
+}
+
+/// Computes the accuracy of a labeling after optimally matching its cluster IDs to a reference
+///
+/// Cluster IDs are arbitrary, so raw accuracy is meaningless. This builds the contingency
+/// matrix of the two labelings, finds the one-to-one mapping from predicted to true IDs that
+/// maximizes the number of agreeing points (Hungarian algorithm), and returns the fraction of
+/// points that agree under that mapping. Predicted clusters left unmatched (when there are
+/// more predicted than true clusters) count as wrong.
+///
+/// # Arguments
+/// * `labels_true` - Reference label of each data point
+/// * `labels_pred` - Predicted cluster assignment of each data point
+///
+/// # Returns
+/// * `Result<f64>` - Accuracy in [0, 1] or error
+pub fn best_match_accuracy(labels_true: &[usize], labels_pred: &[usize]) -> Result<f64> {
+    if labels_true.len() != labels_pred.len() {
+        return Err(anyhow!(
+            "Labelings have different lengths: {} and {}",
+            labels_true.len(),
+            labels_pred.len()
+        ));
+    }
+    if labels_true.is_empty() {
+        return Err(anyhow!("Empty labelings"));
+    }
+    
+    let index_of = |labels: &[usize]| -> HashMap<usize, usize> {
+        let mut ids: Vec<usize> = labels.iter().cloned().collect::<HashSet<_>>().into_iter().collect();
+        ids.sort_unstable();
+        ids.into_iter().enumerate().map(|(i, id)| (id, i)).collect()
+    };
+    let true_index = index_of(labels_true);
+    let pred_index = index_of(labels_pred);
+    
+    let mut contingency = vec![vec![0.0; true_index.len()]; pred_index.len()];
+    for (t, p) in labels_true.iter().zip(labels_pred.iter()) {
+        contingency[pred_index[p]][true_index[t]] += 1.0;
+    }
+    
+    let matching = max_weight_matching(&contingency);
+    let correct: f64 = matching
+        .iter()
+        .enumerate()
+        .filter_map(|(row, col)| col.map(|col| contingency[row][col]))
+        .sum();
+    
+    Ok(correct / labels_true.len() as f64)
+}
+
+/// Computes the adjusted Rand index (ARI) between two labelings of the same points
+///
+/// The Rand index is the fraction of point pairs on which the labelings agree (both put the
+/// pair together, or both apart); the ARI rescales it so that random labelings score 0 in
+/// expectation and identical partitions score 1. Label values only matter up to renaming.
+///
+/// # Arguments
+/// * `labels_a` - Cluster label of each data point in the first labeling
+/// * `labels_b` - Cluster label of each data point in the second labeling
+///
+/// # Returns
+/// * `Result<f64>` - ARI in [-1, 1] (1 for identical partitions), or error if the labelings
+///   are empty or have different lengths
+pub fn adjusted_rand_index(labels_a: &[usize], labels_b: &[usize]) -> Result<f64> {
+    if labels_a.len() != labels_b.len() {
+        return Err(anyhow!(
+            "Labelings have different lengths: {} and {}",
+            labels_a.len(),
+            labels_b.len()
+        ));
+    }
+    if labels_a.is_empty() {
+        return Err(anyhow!("Empty labelings"));
+    }
+    Ok(ari(labels_a, labels_b))
+}
+
+/// Computes the adjusted Rand index between every pair of clusterings of the same points
+///
+/// Useful for parameter sweeps: a block of runs that agree with each other marks a stable
+/// region of the parameter space. Outliers are treated as one extra group, distinct from
+/// every cluster, so two runs agree on a point pair when both call it noise.
+///
+/// # Arguments
+/// * `results` - Clusterings of the same data points
+///
+/// # Returns
+/// * `Result<Array2<f64>>` - Symmetric matrix whose entry `(i, j)` is the ARI between
+///   `results[i]` and `results[j]` (1 on the diagonal), or error if the clusterings cover
+///   different numbers of points
+pub fn ari_matrix(results: &[ClusteringResult]) -> Result<Array2<f64>> {
+    let labels: Vec<Vec<Option<usize>>> = results.iter().map(|result| result.labels()).collect();
+    if let Some(first) = labels.first() {
+        if let Some(other) = labels.iter().find(|other| other.len() != first.len()) {
+            return Err(anyhow!(
+                "Clusterings cover different numbers of points: {} and {}",
+                first.len(),
+                other.len()
+            ));
+        }
+    }
+    
+    let n = results.len();
+    let mut matrix = Array2::<f64>::eye(n);
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let score = ari(&labels[i], &labels[j]);
+            matrix[[i, j]] = score;
+            matrix[[j, i]] = score;
+        }
+    }
+    Ok(matrix)
+}
+
+/// Adjusted Rand index from the contingency table of two equally long labelings
+fn ari<T: Eq + std::hash::Hash + Copy>(labels_a: &[T], labels_b: &[T]) -> f64 {
+    let pairs = |count: usize| (count * count.saturating_sub(1) / 2) as f64;
+    
+    let mut contingency: HashMap<(T, T), usize> = HashMap::new();
+    let mut counts_a: HashMap<T, usize> = HashMap::new();
+    let mut counts_b: HashMap<T, usize> = HashMap::new();
+    for (&a, &b) in labels_a.iter().zip(labels_b.iter()) {
+        *contingency.entry((a, b)).or_insert(0) += 1;
+        *counts_a.entry(a).or_insert(0) += 1;
+        *counts_b.entry(b).or_insert(0) += 1;
+    }
+    
+    let index: f64 = contingency.values().map(|&count| pairs(count)).sum();
+    let sum_a: f64 = counts_a.values().map(|&count| pairs(count)).sum();
+    let sum_b: f64 = counts_b.values().map(|&count| pairs(count)).sum();
+    let expected = sum_a * sum_b / pairs(labels_a.len()).max(1.0);
+    let max_index = (sum_a + sum_b) / 2.0;
+    
+    // Only two identical trivial partitions (one cluster, or all singletons) reach this
+    if max_index == expected {
+        return 1.0;
+    }
+    (index - expected) / (max_index - expected)
+}
+
+/// Solves the assignment problem on a (possibly rectangular) weight matrix with the Hungarian
+/// algorithm, maximizing the total weight
+///
+/// Returns, for every row, the column assigned to it (`None` when there are more rows than columns).
+fn max_weight_matching(weights: &[Vec<f64>]) -> Vec<Option<usize>> {
+    let n_rows = weights.len();
+    let n_cols = weights.first().map_or(0, |row| row.len());
+    let size = n_rows.max(n_cols);
+    if size == 0 {
+        return Vec::new();
+    }
+    
+    // Square cost matrix for minimization, padded with zero-weight entries
+    let max_weight = weights.iter().flatten().cloned().fold(0.0, f64::max);
+    let cost = |i: usize, j: usize| -> f64 {
+        let w = if i < n_rows && j < n_cols { weights[i][j] } else { 0.0 };
+        max_weight - w
+    };
+    
+    // Shortest augmenting path formulation with potentials (1-indexed, column 0 is a sentinel)
+    let mut u = vec![0.0; size + 1];
+    let mut v = vec![0.0; size + 1];
+    let mut col_owner = vec![0usize; size + 1];
+    let mut way = vec![0usize; size + 1];
+    for row in 1..=size {
+        col_owner[0] = row;
+        let mut j0 = 0;
+        let mut min_to = vec![f64::INFINITY; size + 1];
+        let mut used = vec![false; size + 1];
+        loop {
+            used[j0] = true;
+            let i0 = col_owner[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1..=size {
+                if used[j] {
+                    continue;
+                }
+                let reduced = cost(i0 - 1, j - 1) - u[i0] - v[j];
+                if reduced < min_to[j] {
+                    min_to[j] = reduced;
+                    way[j] = j0;
+                }
+                if min_to[j] < delta {
+                    delta = min_to[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=size {
+                if used[j] {
+                    u[col_owner[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_to[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if col_owner[j0] == 0 {
+                break;
+            }
+        }
+        loop {
+            let j1 = way[j0];
+            col_owner[j0] = col_owner[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+    
+    let mut matching = vec![None; n_rows];
+    for (j, &owner) in col_owner.iter().enumerate().skip(1) {
+        let row = owner - 1;
+        if row < n_rows && j - 1 < n_cols {
+            matching[row] = Some(j - 1);
+        }
+    }
+    matching
+}
+
+/// Computes the proportion of variance explained by a clustering (R²-style score)
+///
+/// Returns `1 - within_SS / total_SS`, where `within_SS` is the sum of squared distances of
+/// points to their cluster centroid and `total_SS` the sum of squared distances to the global
+/// mean. Outliers are excluded from both sums. Data without any variance scores 1.0.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+///
+/// # Returns
+/// * `Result<f64>` - Explained variance in [0, 1] (higher is better) or error
+pub fn variance_explained(data: &[Vec<f64>], result: &ClusteringResult) -> Result<f64> {
+    let (clustered_data, clustered_assignments): (Vec<Vec<f64>>, Vec<usize>) = result
+        .clusters
+        .iter()
+        .flat_map(|(&cluster_id, members)| members.iter().map(move |&idx| (data[idx].clone(), cluster_id)))
+        .unzip();
+    if clustered_data.is_empty() {
+        return Err(anyhow!("Clustering has no clustered points"));
+    }
+    
+    let mean = global_mean(&clustered_data);
+    let total_ss: f64 = clustered_data
+        .iter()
+        .map(|point| euclidean_distance(point, &mean).powi(2))
+        .sum();
+    let within_ss = inertia(&clustered_data, &clustered_assignments)?;
+    
+    if total_ss == 0.0 {
+        return Ok(1.0);
+    }
+    Ok(1.0 - within_ss / total_ss)
+}
+
+/// Computes the one-way ANOVA F-statistic of every feature, with clusters as groups
+///
+/// For each feature, F is the between-cluster mean square `SS_between / (k - 1)` over the
+/// within-cluster mean square `SS_within / (n - k)`, for `n` clustered points in `k`
+/// clusters. A high F means the feature separates the clusters well, which makes the scores
+/// useful for ranking features. Outliers are excluded. A feature that is constant within
+/// every cluster scores infinity, or 0.0 if it is constant overall.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+///
+/// # Returns
+/// * `Result<Vec<f64>>` - F-statistic per feature, or error if there are fewer than 2
+///   clusters or no more clustered points than clusters
+pub fn anova_f_scores(data: &[Vec<f64>], result: &ClusteringResult) -> Result<Vec<f64>> {
+    let n_clusters = result.clusters.len();
+    let n_points: usize = result.clusters.values().map(|members| members.len()).sum();
+    if n_clusters < 2 {
+        return Err(anyhow!("ANOVA needs at least 2 clusters, got {}", n_clusters));
+    }
+    if n_points <= n_clusters {
+        return Err(anyhow!(
+            "ANOVA needs more clustered points ({}) than clusters ({})",
+            n_points,
+            n_clusters
+        ));
+    }
+    
+    let clustered: Vec<Vec<f64>> =
+        result.clusters.values().flatten().map(|&idx| data[idx].clone()).collect();
+    let mean = global_mean(&clustered);
+    let mut between_ss = vec![0.0; mean.len()];
+    let mut within_ss = vec![0.0; mean.len()];
+    for members in result.clusters.values() {
+        let cluster_mean = mean_of_rows(data, members);
+        for (dim, &value) in cluster_mean.iter().enumerate() {
+            between_ss[dim] += members.len() as f64 * (value - mean[dim]).powi(2);
+            within_ss[dim] += members.iter().map(|&idx| (data[idx][dim] - value).powi(2)).sum::<f64>();
+        }
+    }
+    
+    let between_df = (n_clusters - 1) as f64;
+    let within_df = (n_points - n_clusters) as f64;
+    Ok(between_ss
+        .iter()
+        .zip(within_ss.iter())
+        .map(|(&between, &within)| match (between > 0.0, within > 0.0) {
+            (_, true) => (between / between_df) / (within / within_df),
+            (true, false) => f64::INFINITY,
+            (false, false) => 0.0,
+        })
+        .collect())
+}
+
+/// Computes the fraction of points that were declared outliers
+///
+/// # Arguments
+/// * `result` - The clustering result
+///
+/// # Returns
+/// * `f64` - `outliers / points`, or 0.0 for an empty result
+pub fn outlier_fraction(result: &ClusteringResult) -> f64 {
+    if result.assignments.is_empty() {
+        return 0.0;
+    }
+    result.outliers.len() as f64 / result.assignments.len() as f64
+}
+
+/// Computes a per-point anomaly score combining local density and distance to the clusters
+///
+/// Blends two signals, each min-max normalized to [0, 1] over the dataset: the Local Outlier
+/// Factor (`local_outlier_factor`) and the distance to the nearest cluster centroid
+/// (`compute_centroids`). The score is their average, so a point must be both locally sparse
+/// and far from every cluster to rank at the top. A signal that is constant over the dataset
+/// contributes 0.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+/// * `k` - Number of neighbors for the LOF
+///
+/// # Returns
+/// * `Result<Vec<f64>>` - Anomaly score per point in [0, 1] (higher is more anomalous), or error
+pub fn anomaly_score(data: &[Vec<f64>], result: &ClusteringResult, k: usize) -> Result<Vec<f64>> {
+    if data.len() != result.assignments.len() {
+        return Err(anyhow!(
+            "Data has {} points but the clustering covers {}",
+            data.len(),
+            result.assignments.len()
+        ));
+    }
+    let centroids: Vec<Vec<f64>> = compute_centroids(data, result).into_values().collect();
+    if centroids.is_empty() {
+        return Err(anyhow!("The clustering has no clusters"));
+    }
+    
+    let lof = min_max_normalize(local_outlier_factor(data, k)?);
+    let centroid_distance = min_max_normalize(
+        data.iter()
+            .map(|point| {
+                centroids
+                    .iter()
+                    .map(|c| euclidean_distance(point, c))
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect(),
+    );
+    
+    Ok(lof.iter().zip(centroid_distance.iter()).map(|(a, b)| (a + b) / 2.0).collect())
+}
+
+/// Rescales values linearly to [0, 1]; constant inputs map to 0
+fn min_max_normalize(values: Vec<f64>) -> Vec<f64> {
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let range = max - min;
+    values
+        .into_iter()
+        .map(|v| if range > 0.0 { (v - min) / range } else { 0.0 })
+        .collect()
+}
+
+/// Computes the Bayesian information criterion of a KMeans clustering (X-means formulation)
+///
+/// Models the data as a mixture of spherical Gaussians with one shared variance, centered at
+/// the cluster centroids and weighted by cluster size (Pelleg & Moore, 2000). The number of
+/// free parameters is `(K - 1) + M·K + 1` for `K` clusters in `M` dimensions. Outliers are
+/// ignored. Unlike the usual statistics convention, higher is better here.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+///
+/// # Returns
+/// * `f64` - The BIC (higher is better); negative infinity when there are no more points than
+///   clusters, positive infinity when every point sits exactly on its centroid
+pub fn kmeans_bic(data: &[Vec<f64>], result: &ClusteringResult) -> f64 {
+    let centroids = compute_centroids(data, result);
+    let n_clusters = centroids.len() as f64;
+    let n_points: usize = result.clusters.values().map(|indices| indices.len()).sum();
+    let r = n_points as f64;
+    if r <= n_clusters || data.is_empty() {
+        return f64::NEG_INFINITY;
+    }
+    let n_dims = data[0].len() as f64;
+    
+    // Maximum likelihood estimate of the shared variance
+    let squared_error: f64 = result
+        .clusters
+        .iter()
+        .filter_map(|(cluster_id, indices)| centroids.get(cluster_id).map(|c| (c, indices)))
+        .map(|(centroid, indices)| {
+            indices
+                .iter()
+                .map(|&idx| euclidean_distance(&data[idx], centroid).powi(2))
+                .sum::<f64>()
+        })
+        .sum();
+    let variance = squared_error / (r - n_clusters);
+    
+    let log_likelihood: f64 = result
+        .clusters
+        .values()
+        .filter(|indices| !indices.is_empty())
+        .map(|indices| {
+            let r_n = indices.len() as f64;
+            -r_n / 2.0 * (2.0 * std::f64::consts::PI).ln() - r_n * n_dims / 2.0 * variance.ln()
+                - (r_n - n_clusters) / 2.0
+                + r_n * r_n.ln()
+                - r_n * r.ln()
+        })
+        .sum();
+    let n_parameters = (n_clusters - 1.0) + n_dims * n_clusters + 1.0;
+    
+    log_likelihood - n_parameters / 2.0 * r.ln()
+}
+
+/// Computes the minimum description length (MDL) of a clustering, in bits
+///
+/// Two-part code: the model cost spends `½·log₂(N)` bits on each parameter (the `M·K`
+/// centroid coordinates, the label frequencies and one shared variance), and the data cost
+/// encodes each point's label under the label frequencies plus its residual under a spherical
+/// Gaussian with the shared maximum likelihood variance. Outliers get a label of their own
+/// and residuals from the global mean, so algorithms that leave points unclustered are
+/// comparable with those that do not. Residual costs are for data quantized at a fixed
+/// precision, up to a constant that cancels when comparing clusterings of the same data.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+///
+/// # Returns
+/// * `f64` - The description length (lower is better); negative infinity when every point
+///   sits exactly on its center, 0 for empty data
+pub fn description_length(data: &[Vec<f64>], result: &ClusteringResult) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let n = data.len() as f64;
+    let n_dims = data[0].len() as f64;
+    
+    let centroids = compute_centroids(data, result);
+    let global_center = global_mean(data);
+    let mut groups: Vec<(&[f64], &[usize])> = centroids
+        .iter()
+        .map(|(cluster_id, centroid)| (centroid.as_slice(), result.clusters[cluster_id].as_slice()))
+        .collect();
+    if !result.outliers.is_empty() {
+        groups.push((global_center.as_slice(), result.outliers.as_slice()));
+    }
+    
+    let squared_error: f64 = groups
+        .iter()
+        .flat_map(|&(center, indices)| {
+            indices.iter().map(move |&idx| euclidean_distance(&data[idx], center).powi(2))
+        })
+        .sum();
+    let variance = squared_error / (n * n_dims);
+    let residual_cost = n * n_dims / 2.0 * (2.0 * std::f64::consts::PI * std::f64::consts::E * variance).log2();
+    
+    let label_cost: f64 = groups
+        .iter()
+        .map(|(_, indices)| indices.len() as f64)
+        .filter(|&size| size > 0.0)
+        .map(|size| -size * (size / n).log2())
+        .sum();
+    
+    let n_parameters = n_dims * centroids.len() as f64 + (groups.len() as f64 - 1.0) + 1.0;
+    let model_cost = n_parameters / 2.0 * n.log2();
+    
+    model_cost + label_cost + residual_cost
+}
+
+/// Selects the number of KMeans clusters by splitting clusters while the BIC improves (X-means)
+///
+/// Starts from KMeans with `k_min` clusters. Each round, every cluster is split in two with
+/// KMeans on its own points, and the split is kept when the two-cluster model of those points
+/// has a higher `kmeans_bic` than the one-cluster model. If any splits were kept, KMeans is
+/// refitted on the whole dataset with one more cluster per kept split, capped at `k_max`.
+/// Stops when no split improves the BIC.
+///
+/// # Arguments
+/// * `data` - A 2D array of data points to cluster
+/// * `k_min` - Initial number of clusters (at least 1)
+/// * `k_max` - Maximum number of clusters
+/// * `seed` - Random seed for KMeans initialization (default: 42)
+///
+/// # Returns
+/// * `Result<ClusteringResult>` - The clustering result with the selected number of clusters, or error
+pub fn xmeans(data: &[Vec<f64>], k_min: usize, k_max: usize, seed: Option<u64>) -> Result<ClusteringResult> {
+    if k_min == 0 || k_min > k_max {
+        return Err(anyhow!("Need 1 <= k_min <= k_max, got k_min = {}, k_max = {}", k_min, k_max));
+    }
+    
+    let mut k = k_min;
+    let mut result = kmeans_clustering(data, k, None, None, seed)?;
+    while k < k_max {
+        let mut n_splits = 0;
+        for indices in result.clusters.values() {
+            // A split needs more points than clusters on both models to have a finite BIC
+            if indices.len() <= 2 {
+                continue;
+            }
+            let subset: Vec<Vec<f64>> = indices.iter().map(|&idx| data[idx].clone()).collect();
+            let whole = ClusteringResult::from_clusters(
+                HashMap::from([(0, (0..subset.len()).collect())]),
+                Vec::new(),
+                subset.len(),
+            );
+            let split = kmeans_clustering(&subset, 2, None, None, seed)?;
+            
+            if kmeans_bic(&subset, &split) > kmeans_bic(&subset, &whole) {
+                n_splits += 1;
+            }
+        }
+        
+        if n_splits == 0 {
+            break;
+        }
+        k = (k + n_splits).min(k_max);
+        result = kmeans_clustering(data, k, None, None, seed)?;
+    }
+    
+    Ok(result)
+}
+
+/// Computes the Newman modularity of a clustering on the k-NN graph of the data
+///
+/// The graph is the unweighted, symmetrized k-nearest-neighbor graph (an edge joins two
+/// points when either is among the other's `k` nearest neighbors, found with
+/// `k_nearest_neighbors`). With clusters as communities,
+/// `Q = Σ_c [L_c / m - (d_c / 2m)²]`, where `m` is the number of edges, `L_c` the number of
+/// edges inside community `c` and `d_c` the total degree of its points. Each outlier is its
+/// own community.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+/// * `k` - Number of neighbors per point in the graph
+///
+/// # Returns
+/// * `Result<f64>` - Modularity (at most 1, higher means the clusters match the graph's
+///   community structure; around 0 for random partitions), or error
+pub fn modularity(data: &[Vec<f64>], result: &ClusteringResult, k: usize) -> Result<f64> {
+    if data.len() != result.assignments.len() {
+        return Err(anyhow!(
+            "Data has {} points but the clustering covers {}",
+            data.len(),
+            result.assignments.len()
+        ));
+    }
+    
+    let mut edges: HashSet<(usize, usize)> = HashSet::new();
+    for (i, neighbors) in k_nearest_neighbors(data, k)?.into_iter().enumerate() {
+        for (j, _) in neighbors {
+            edges.insert((i.min(j), i.max(j)));
+        }
+    }
+    if edges.is_empty() {
+        return Ok(0.0);
+    }
+    
+    // Outliers get community IDs past every cluster ID so that each is a singleton
+    let max_cluster = result.clusters.keys().cloned().max().unwrap_or(0);
+    let community: Vec<usize> = result
+        .labels()
+        .into_iter()
+        .enumerate()
+        .map(|(i, label)| label.unwrap_or(max_cluster + 1 + i))
+        .collect();
+    
+    let mut internal_edges: HashMap<usize, f64> = HashMap::new();
+    let mut total_degree: HashMap<usize, f64> = HashMap::new();
+    for &(i, j) in edges.iter() {
+        if community[i] == community[j] {
+            *internal_edges.entry(community[i]).or_insert(0.0) += 1.0;
+        }
+        *total_degree.entry(community[i]).or_insert(0.0) += 1.0;
+        *total_degree.entry(community[j]).or_insert(0.0) += 1.0;
+    }
+    
+    let m = edges.len() as f64;
+    Ok(total_degree
+        .iter()
+        .map(|(c, &degree)| internal_edges.get(c).unwrap_or(&0.0) / m - (degree / (2.0 * m)).powi(2))
+        .sum())
+}
+
+/// Computes the Xie-Beni index of a fuzzy clustering
+///
+/// `XB = Σ_i Σ_c u_ic² ‖x_i - v_c‖² / (N · min_{c≠c'} ‖v_c - v_c'‖²)`: the membership-weighted
+/// within-cluster variation (with the standard fuzzifier 2) relative to the separation of the
+/// closest pair of centroids. Lower is better.
+///
+/// # Arguments
+/// * `data` - The data points
+/// * `centroids` - The cluster centroids
+/// * `membership` - Membership degree of every point in every cluster (N × K)
+///
+/// # Returns
+/// * `Result<f64>` - The Xie-Beni index (infinity if two centroids coincide), or error
+pub fn xie_beni_index(data: &[Vec<f64>], centroids: &[Vec<f64>], membership: &[Vec<f64>]) -> Result<f64> {
+    if data.is_empty() {
+        return Err(anyhow!("Empty input data"));
+    }
+    if centroids.len() < 2 {
+        return Err(anyhow!("The Xie-Beni index needs at least 2 centroids, got {}", centroids.len()));
+    }
+    if membership.len() != data.len() {
+        return Err(anyhow!(
+            "Membership has {} rows but there are {} data points",
+            membership.len(),
+            data.len()
+        ));
+    }
+    if let Some(row) = membership.iter().position(|m| m.len() != centroids.len()) {
+        return Err(anyhow!(
+            "Membership row {} has {} entries, expected one per centroid ({})",
+            row,
+            membership[row].len(),
+            centroids.len()
+        ));
+    }
+    
+    let compactness: f64 = data
+        .iter()
+        .zip(membership.iter())
+        .map(|(point, memberships)| {
+            centroids
+                .iter()
+                .zip(memberships.iter())
+                .map(|(centroid, &u)| u * u * euclidean_distance(point, centroid).powi(2))
+                .sum::<f64>()
+        })
+        .sum();
+    
+    let mut min_separation = f64::INFINITY;
+    for i in 0..centroids.len() {
+        for j in (i + 1)..centroids.len() {
+            min_separation = min_separation.min(euclidean_distance(&centroids[i], &centroids[j]).powi(2));
+        }
+    }
+    
+    if min_separation == 0.0 {
+        return Ok(f64::INFINITY);
+    }
+    Ok(compactness / (data.len() as f64 * min_separation))
+}
+
+/// Measures how much every pair of clusters overlaps in the k-NN graph
+///
+/// For clusters `a` and `b`, the overlap is the fraction of the k-nearest-neighbor slots of
+/// their members (found with `k_nearest_neighbors`) that point into the other cluster. Well
+/// separated clusters score near 0 even when non-globular, intertwined ones score high, which
+/// makes this more sensitive than centroid distances. Outliers are not a cluster, but their
+/// slots count in the denominator when they are neighbors.
+///
+/// # Arguments
+/// * `data` - The data points that were clustered
+/// * `result` - The clustering result
+/// * `k` - Number of neighbors per point
+///
+/// # Returns
+/// * `Result<Vec<(usize, usize, f64)>>` - `(a, b, overlap)` for every cluster pair with `a < b`,
+///   sorted by cluster IDs, or error
+pub fn snn_overlap(data: &[Vec<f64>], result: &ClusteringResult, k: usize) -> Result<Vec<(usize, usize, f64)>> {
+    if data.len() != result.assignments.len() {
+        return Err(anyhow!(
+            "Data has {} points but the clustering covers {}",
+            data.len(),
+            result.assignments.len()
+        ));
+    }
+    
+    let labels = result.labels();
+    let mut slots: HashMap<usize, usize> = HashMap::new();
+    let mut cross: HashMap<(usize, usize), usize> = HashMap::new();
+    for (i, neighbors) in k_nearest_neighbors(data, k)?.into_iter().enumerate() {
+        let Some(a) = labels[i] else { continue };
+        *slots.entry(a).or_insert(0) += neighbors.len();
+        for (j, _) in neighbors {
+            if let Some(b) = labels[j] {
+                if a != b {
+                    *cross.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    
+    let mut ids: Vec<usize> = result.clusters.keys().cloned().collect();
+    ids.sort_unstable();
+    let mut overlaps = Vec::new();
+    for (idx, &a) in ids.iter().enumerate() {
+        for &b in ids[idx + 1..].iter() {
+            let total = slots.get(&a).unwrap_or(&0) + slots.get(&b).unwrap_or(&0);
+            let shared = *cross.get(&(a, b)).unwrap_or(&0);
+            let overlap = if total > 0 { shared as f64 / total as f64 } else { 0.0 };
+            overlaps.push((a, b, overlap));
+        }
+    }
+    
+    Ok(overlaps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn prediction_strength_never_picks_unmeasured_k() {
+        // Only singletons: nothing to measure
+        let singletons = [vec![0], vec![1], vec![2]];
+        assert_eq!(min_pair_agreement(singletons.iter(), &[0, 0, 0]), 0.0);
+        // The singleton is skipped; the weakest cluster decides
+        let clusters = [vec![0, 1, 2, 3], vec![4, 5], vec![6]];
+        let predicted = [0, 0, 1, 1, 2, 3, 4];
+        assert_eq!(min_pair_agreement(clusters.iter(), &predicted), 0.0);
+        let predicted = [0, 0, 0, 1, 2, 2, 4];
+        assert!((min_pair_agreement(clusters.iter(), &predicted) - 0.5).abs() < 1e-12);
+        
+        // With k up to half the data, the largest k leaves one point per test cluster
+        let data: Vec<Vec<f64>> = (0..8).map(|i| vec![i as f64, (i * i) as f64]).collect();
+        let (k, strengths) = prediction_strength(&data, 1..data.len() / 2 + 1, 3).unwrap();
+        assert_eq!(strengths.len(), 4);
+        assert_eq!(strengths[0], 1.0);
+        assert_eq!(strengths[3], 0.0);
+        assert!(k < 4);
+    }
+    
+    #[test]
+    fn best_match_accuracy_finds_optimal_matching() {
+        // Renamed clusters match perfectly
+        assert_eq!(best_match_accuracy(&[0, 0, 1, 1, 2], &[5, 5, 3, 3, 9]).unwrap(), 1.0);
+        
+        // Contingency (predicted x true) [[3, 2], [2, 0]]: greedily matching the 3 first
+        // scores 3, the optimal crossed matching scores 4
+        let labels_true = [0, 0, 0, 1, 1, 0, 0];
+        let labels_pred = [0, 0, 0, 0, 0, 1, 1];
+        assert!((best_match_accuracy(&labels_true, &labels_pred).unwrap() - 4.0 / 7.0).abs() < 1e-12);
+        
+        // A third predicted cluster has no true cluster left to match
+        assert!((best_match_accuracy(&[0, 0, 1, 1], &[0, 0, 1, 2]).unwrap() - 0.75).abs() < 1e-12);
+    }
+    
+    #[test]
+    fn max_weight_matching_agrees_with_brute_force() {
+        fn permutations(items: Vec<usize>) -> Vec<Vec<usize>> {
+            if items.len() <= 1 {
+                return vec![items];
+            }
+            (0..items.len())
+                .flat_map(|i| {
+                    let mut rest = items.clone();
+                    let first = rest.remove(i);
+                    permutations(rest).into_iter().map(move |mut p| {
+                        p.insert(0, first);
+                        p
+                    })
+                })
+                .collect()
+        }
+        
+        let mut rng = Xoshiro256Plus::seed_from_u64(7);
+        for (n_rows, n_cols) in [(4, 4), (3, 5), (5, 3)] {
+            for _ in 0..20 {
+                let weights: Vec<Vec<f64>> = (0..n_rows)
+                    .map(|_| (0..n_cols).map(|_| rng.gen_range(0..10) as f64).collect())
+                    .collect();
+                let size = n_rows.max(n_cols);
+                let best = permutations((0..size).collect())
+                    .into_iter()
+                    .map(|cols| {
+                        (0..n_rows).filter(|&r| cols[r] < n_cols).map(|r| weights[r][cols[r]]).sum::<f64>()
+                    })
+                    .fold(f64::NEG_INFINITY, f64::max);
+                
+                let matching = max_weight_matching(&weights);
+                let mut used = HashSet::new();
+                let total: f64 = matching
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(r, c)| c.map(|c| (r, c)))
+                    .inspect(|&(_, c)| assert!(used.insert(c), "column {} matched twice", c))
+                    .map(|(r, c)| weights[r][c])
+                    .sum();
+                assert_eq!(total, best);
+            }
+        }
+    }
+    
+    #[test]
+    fn kmeans_bic_prefers_true_cluster_count() {
+        let square = [[0.0, 0.0], [0.0, 1.0], [1.0, 0.0], [1.0, 1.0]];
+        let data: Vec<Vec<f64>> = [0.0, 10.0]
+            .iter()
+            .flat_map(|&offset| square.iter().map(move |p| vec![p[0] + offset, p[1] + offset]))
+            .collect();
+        let one = ClusteringResult::from_clusters(HashMap::from([(1, (0..8).collect())]), Vec::new(), 8);
+        let two = ClusteringResult::from_clusters(
+            HashMap::from([(1, (0..4).collect()), (2, (4..8).collect())]),
+            Vec::new(),
+            8,
+        );
+        let four = ClusteringResult::from_clusters(
+            HashMap::from([(1, vec![0, 1]), (2, vec![2, 3]), (3, vec![4, 5]), (4, vec![6, 7])]),
+            Vec::new(),
+            8,
+        );
+        
+        let bic_two = kmeans_bic(&data, &two);
+        assert!(bic_two > kmeans_bic(&data, &one));
+        assert!(bic_two > kmeans_bic(&data, &four));
+        
+        let singletons: HashMap<usize, Vec<usize>> = (0..8).map(|i| (i + 1, vec![i])).collect();
+        let singletons = ClusteringResult::from_clusters(singletons, Vec::new(), 8);
+        assert_eq!(kmeans_bic(&data, &singletons), f64::NEG_INFINITY);
+    }
+    
+    #[test]
+    fn variance_explained_ignores_outliers() {
+        let data = vec![vec![0.0], vec![2.0], vec![10.0], vec![12.0], vec![100.0]];
+        let clusters = HashMap::from([(1, vec![0, 1]), (2, vec![2, 3])]);
+        let result = ClusteringResult::from_clusters(clusters, vec![4], data.len());
+        
+        // Clustered points have mean 6: total_SS = 36 + 16 + 16 + 36 = 104, within_SS = 4 * 1
+        let score = variance_explained(&data, &result).unwrap();
+        assert!((score - (1.0 - 4.0 / 104.0)).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::{anyhow, Result};
+use hnsw_rs::prelude::*;
+use std::collections::HashMap;
+
+use crate::utils::euclidean_distance;
+
+/// Finds the `k` nearest neighbors of every data point using an HNSW index
+///
+/// The search is approximate, but the returned distances are exact Euclidean
+/// distances. A point is never returned as its own neighbor.
+///
+/// # Arguments
+/// * `data` - A slice of vectors representing the data points
+/// * `k` - Number of neighbors to find per point
+///
+/// # Returns
+/// * `Result<Vec<Vec<(usize, f64)>>>` - Per point, up to `k` `(index, distance)` pairs sorted by
+///   ascending distance, or error
+pub fn k_nearest_neighbors(data: &[Vec<f64>], k: usize) -> Result<Vec<Vec<(usize, f64)>>> {
+    if data.is_empty() {
+        return Err(anyhow!("Empty input data"));
+    }
+    if k == 0 {
+        return Err(anyhow!("k must be at least 1"));
+    }
+    
+    // Same construction parameters as perform_dimension_reduction
+    let ef_c = 50;
+    let max_nb_connection = 70;
+    let nb_layer = 16.min((data.len() as f64).ln().trunc() as usize).max(1);
+    
+    let hnsw = Hnsw::<f64, DistL2>::new(max_nb_connection, data.len(), nb_layer, ef_c, DistL2 {});
+    let data_with_id: Vec<(&Vec<f64>, usize)> = data.iter().zip(0..data.len()).collect();
+    hnsw.parallel_insert(&data_with_id);
+    
+    // Ask for one extra neighbor since each point usually finds itself
+    let ef_search = ef_c.max(k + 1);
+    let results = hnsw.parallel_search(data, k + 1, ef_search);
+    
+    Ok(results
+        .into_iter()
+        .enumerate()
+        .map(|(idx, found)| {
+            let mut neighbors: Vec<(usize, f64)> = found
+                .iter()
+                .filter(|n| n.d_id != idx)
+                .map(|n| (n.d_id, euclidean_distance(&data[idx], &data[n.d_id])))
+                .collect();
+            neighbors.sort_by(|a, b| a.1.total_cmp(&b.1));
+            neighbors.truncate(k);
+            neighbors
+        })
+        .collect())
+}
+
+/// Computes the sorted k-distance curve of a dataset
+///
+/// Each point's k-distance is the distance to its `k`-th nearest neighbor (excluding the
+/// point itself). Plotted in ascending order, the knee of this curve is the usual visual
+/// guide for choosing DBSCAN's `eps`.
+///
+/// # Arguments
+/// * `data` - A slice of vectors representing the data points
+/// * `k` - Neighbor rank to use
+///
+/// # Returns
+/// * `Result<Vec<f64>>` - The k-distances sorted in ascending order, or error
+pub fn k_distances(data: &[Vec<f64>], k: usize) -> Result<Vec<f64>> {
+    if data.len() <= k {
+        return Err(anyhow!("Need more than k = {} data points, got {}", k, data.len()));
+    }
+    
+    let neighbors = k_nearest_neighbors(data, k)?;
+    let mut distances: Vec<f64> = neighbors
+        .iter()
+        .filter_map(|point_neighbors| point_neighbors.last().map(|&(_, d)| d))
+        .collect();
+    distances.sort_by(|a, b| a.total_cmp(b));
+    Ok(distances)
+}
+
+/// Computes the Local Outlier Factor (LOF) of every data point
+///
+/// A point's LOF is the average local reachability density of its `k` nearest neighbors
+/// divided by its own. Values around 1 mean the point is as dense as its neighborhood, values
+/// well above 1 flag points in sparser regions than their neighbors (local outliers).
+///
+/// # Arguments
+/// * `data` - A slice of vectors representing the data points
+/// * `k` - Number of neighbors defining the local neighborhood
+///
+/// # Returns
+/// * `Result<Vec<f64>>` - LOF per point, or error
+pub fn local_outlier_factor(data: &[Vec<f64>], k: usize) -> Result<Vec<f64>> {
+    if data.len() <= k {
+        return Err(anyhow!("Need more than k = {} data points, got {}", k, data.len()));
+    }
+    
+    let neighbors = k_nearest_neighbors(data, k)?;
+    let k_distance: Vec<f64> = neighbors
+        .iter()
+        .map(|point_neighbors| point_neighbors.last().map_or(0.0, |&(_, d)| d))
+        .collect();
+    
+    // Local reachability density; the small offset keeps duplicated points finite
+    let lrd: Vec<f64> = neighbors
+        .iter()
+        .map(|point_neighbors| {
+            let mean_reach = point_neighbors
+                .iter()
+                .map(|&(j, d)| d.max(k_distance[j]))
+                .sum::<f64>()
+                / point_neighbors.len() as f64;
+            1.0 / (mean_reach + 1e-10)
+        })
+        .collect();
+    
+    Ok(neighbors
+        .iter()
+        .enumerate()
+        .map(|(i, point_neighbors)| {
+            let mean_lrd = point_neighbors.iter().map(|&(j, _)| lrd[j]).sum::<f64>() / point_neighbors.len() as f64;
+            mean_lrd / lrd[i]
+        })
+        .collect())
+}
+
+/// Labels the connected components of the k-NN graph of a dataset
+///
+/// Two points are connected when either is among the other's `k` nearest neighbors. A graph
+/// with several components breaks the assumptions of `perform_dimension_reduction`, whose
+/// embedding then scatters the smaller components arbitrarily; increase `k` or process the
+/// components separately.
+///
+/// # Arguments
+/// * `data` - A slice of vectors representing the data points
+/// * `k` - Number of neighbors per point
+///
+/// # Returns
+/// * `Result<Vec<usize>>` - Component label per point, numbered `0, 1, ...` in order of first
+///   appearance, or error
+pub fn connected_components(data: &[Vec<f64>], k: usize) -> Result<Vec<usize>> {
+    let neighbors = k_nearest_neighbors(data, k)?;
+    
+    let mut parent: Vec<usize> = (0..data.len()).collect();
+    fn find(parent: &mut [usize], mut x: usize) -> usize {
+        while parent[x] != x {
+            parent[x] = parent[parent[x]];
+            x = parent[x];
+        }
+        x
+    }
+    for (i, point_neighbors) in neighbors.iter().enumerate() {
+        for &(j, _) in point_neighbors {
+            let (root_i, root_j) = (find(&mut parent, i), find(&mut parent, j));
+            if root_i != root_j {
+                parent[root_j] = root_i;
+            }
+        }
+    }
+    
+    let mut labels_by_root: HashMap<usize, usize> = HashMap::new();
+    Ok((0..data.len())
+        .map(|i| {
+            let root = find(&mut parent, i);
+            let next = labels_by_root.len();
+            *labels_by_root.entry(root).or_insert(next)
+        })
+        .collect())
+}
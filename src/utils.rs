@@ -1,4 +1,5 @@
-use ndarray::Array2;
+use anyhow::{anyhow, Result};
+use ndarray::{Array2, ArrayView2};
 
 /// Convert a 2D vector to ndarray Array2<f64>
 ///
@@ -6,21 +7,107 @@ use ndarray::Array2;
 /// * `data` - The 2D vector to convert
 ///
 /// # Returns
-/// * `Array2<f64>` - The resulting 2D array
-pub fn vec_to_array2(data: &[Vec<f64>]) -> Array2<f64> {
-    if data.is_empty() {
-        return Array2::from_shape_vec((0, 0), vec![]).unwrap();
+/// * `Result<Array2<f64>>` - The resulting 2D array, or error naming the first row whose
+///   length differs from the first row's
+pub fn vec_to_array2(data: &[Vec<f64>]) -> Result<Array2<f64>> {
+    let ncols = validate_row_lengths(data)?;
+    let flat_data = flatten_rows(data);
+    
+    Array2::from_shape_vec((data.len(), ncols), flat_data)
+        .map_err(|e| anyhow!("Failed to reshape data: {}", e))
+}
+
+/// Concatenate data points into one row-major buffer
+///
+/// With the `parallel` feature the rows are copied on the rayon thread pool; the output is
+/// the same either way.
+pub(crate) fn flatten_rows<T: Copy + Send + Sync>(data: &[Vec<T>]) -> Vec<T> {
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        data.par_iter().flat_map_iter(|v| v.iter().cloned()).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        data.iter().flat_map(|v| v.iter().cloned()).collect()
+    }
+}
+
+/// Check that every data point has the same number of dimensions as the first
+///
+/// # Arguments
+/// * `data` - The data points
+///
+/// # Returns
+/// * `Result<usize>` - The common number of dimensions (0 for empty data), or error naming
+///   the first row whose length differs
+pub fn validate_row_lengths<T>(data: &[Vec<T>]) -> Result<usize> {
+    let ncols = data.first().map_or(0, |row| row.len());
+    if let Some((i, row)) = data.iter().enumerate().find(|(_, row)| row.len() != ncols) {
+        return Err(anyhow!("Row {} has length {}, expected {}", i, row.len(), ncols));
+    }
+    Ok(ncols)
+}
+
+/// View a flat row-major buffer as a 2D array without copying it
+///
+/// # Arguments
+/// * `data` - The data points, row after row
+/// * `n_rows` - Number of data points
+/// * `n_cols` - Number of dimensions per data point
+///
+/// # Returns
+/// * `Result<ArrayView2<f64>>` - The borrowed 2D view, or error if the buffer is empty or its
+///   length is not `n_rows * n_cols`
+pub fn flat_view(data: &[f64], n_rows: usize, n_cols: usize) -> Result<ArrayView2<'_, f64>> {
+    if n_rows == 0 {
+        return Err(anyhow!("Empty input data"));
+    }
+    if n_rows.checked_mul(n_cols) != Some(data.len()) {
+        return Err(anyhow!(
+            "Flat buffer of length {} does not hold {} rows of {} columns",
+            data.len(),
+            n_rows,
+            n_cols
+        ));
     }
     
-    let nrows = data.len();
-    let ncols = data[0].len();
-    let flat_data: Vec<f64> = data.iter().flat_map(|v| v.iter().cloned()).collect();
+    ArrayView2::from_shape((n_rows, n_cols), data).map_err(|e| anyhow!("Failed to reshape data: {}", e))
+}
+
+/// Remove the listed columns from every data point
+///
+/// Handy to exclude identifier or other non-feature columns before clustering without
+/// slicing the input by hand. Duplicate indices are allowed.
+///
+/// # Arguments
+/// * `data` - The data points
+/// * `ignore_dims` - Column indices to remove
+///
+/// # Returns
+/// * `Result<Vec<Vec<f64>>>` - The data without the listed columns, or error if an index is out of range
+pub fn drop_dims(data: &[Vec<f64>], ignore_dims: &[usize]) -> Result<Vec<Vec<f64>>> {
+    let n_dims = data.first().map_or(0, |v| v.len());
+    if let Some(&dim) = ignore_dims.iter().find(|&&dim| dim >= n_dims) {
+        return Err(anyhow!("Dimension {} is out of range for {}-dimensional data", dim, n_dims));
+    }
+    
+    let mut keep = vec![true; n_dims];
+    for &dim in ignore_dims {
+        keep[dim] = false;
+    }
     
-    Array2::from_shape_vec((nrows, ncols), flat_data).unwrap()
+    Ok(data
+        .iter()
+        .map(|v| v.iter().zip(keep.iter()).filter(|(_, &k)| k).map(|(&x, _)| x).collect())
+        .collect())
 }
 
 /// Compute Euclidean distance between two vectors
 ///
+/// Panics if the vectors have different lengths; use `try_euclidean_distance` for data that
+/// has not been validated.
+///
 /// # Arguments
 /// * `v1` - First vector
 /// * `v2` - Second vector
@@ -28,19 +115,33 @@ pub fn vec_to_array2(data: &[Vec<f64>]) -> Array2<f64> {
 /// # Returns
 /// * `f64` - Euclidean distance
 pub fn euclidean_distance(v1: &[f64], v2: &[f64]) -> f64 {
-    if v1.len() != v2.len() {
-        panic!("Vectors must have the same length");
-    }
+    try_euclidean_distance(v1, v2).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Compute Euclidean distance between two vectors, failing on mismatched lengths
+///
+/// # Arguments
+/// * `v1` - First vector
+/// * `v2` - Second vector
+///
+/// # Returns
+/// * `Result<f64>` - Euclidean distance, or error if the vectors have different lengths
+pub fn try_euclidean_distance(v1: &[f64], v2: &[f64]) -> Result<f64> {
+    check_same_length(v1, v2)?;
     
-    v1.iter()
+    Ok(v1.iter()
         .zip(v2.iter())
         .map(|(&a, &b)| (a - b).powi(2))
         .sum::<f64>()
-        .sqrt()
+        .sqrt())
 }
 
 /// Compute cosine similarity between two vectors
 ///
+/// A zero vector has no direction, so its similarity to any vector is 0.0. Panics if the
+/// vectors have different lengths; use `try_cosine_similarity` for data that has not been
+/// validated.
+///
 /// # Arguments
 /// * `v1` - First vector
 /// * `v2` - Second vector
@@ -48,14 +149,680 @@ pub fn euclidean_distance(v1: &[f64], v2: &[f64]) -> f64 {
 /// # Returns
 /// * `f64` - Cosine similarity (-1 to 1, where 1 means identical direction)
 pub fn cosine_similarity(v1: &[f64], v2: &[f64]) -> f64 {
-    if v1.len() != v2.len() {
-        panic!("Vectors must have the same length");
-    }
+    try_cosine_similarity(v1, v2).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Compute cosine similarity between two vectors, failing on mismatched lengths
+///
+/// # Arguments
+/// * `v1` - First vector
+/// * `v2` - Second vector
+///
+/// # Returns
+/// * `Result<f64>` - Cosine similarity (-1 to 1, 0.0 if either vector is zero), or error if the
+///   vectors have different lengths
+pub fn try_cosine_similarity(v1: &[f64], v2: &[f64]) -> Result<f64> {
+    check_same_length(v1, v2)?;
     
     let dot_product = v1.iter().zip(v2.iter()).map(|(&a, &b)| a * b).sum::<f64>();
     
     let mag1 = v1.iter().map(|&x| x.powi(2)).sum::<f64>().sqrt();
     let mag2 = v2.iter().map(|&x| x.powi(2)).sum::<f64>().sqrt();
+    if mag1 == 0.0 || mag2 == 0.0 {
+        return Ok(0.0);
+    }
+    
+    Ok(dot_product / (mag1 * mag2))
+}
+
+/// Error unless both vectors have the same length
+fn check_same_length(v1: &[f64], v2: &[f64]) -> Result<()> {
+    if v1.len() != v2.len() {
+        return Err(anyhow!("Vectors must have the same length, got {} and {}", v1.len(), v2.len()));
+    }
+    Ok(())
+}
+
+/// Distance metric used by the metric-aware functions of this crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DistanceMetric {
+    /// Straight-line (L2) distance
+    #[default]
+    Euclidean,
+    /// Sum of absolute coordinate differences (L1)
+    Manhattan,
+    /// `1 - cosine_similarity`; zero vectors have distance 1.0 to any other vector, including
+    /// another zero vector
+    Cosine,
+    /// Dynamic time warping between time series, see `dtw_distance`. Not a true metric
+    /// (no triangle inequality), so prefer medoid-based clustering over centroid averaging
+    Dtw {
+        /// Optional Sakoe-Chiba band half-width
+        window: Option<usize>,
+    },
+}
+
+impl DistanceMetric {
+    /// Compute the distance between two vectors under this metric
+    ///
+    /// Panics if the vectors have different lengths (except under DTW); use `try_distance`
+    /// for data that has not been validated.
+    ///
+    /// # Arguments
+    /// * `v1` - First vector
+    /// * `v2` - Second vector
+    ///
+    /// # Returns
+    /// * `f64` - The distance
+    pub fn distance(&self, v1: &[f64], v2: &[f64]) -> f64 {
+        self.try_distance(v1, v2).unwrap_or_else(|e| panic!("{}", e))
+    }
+    
+    /// Compute the distance between two vectors under this metric, failing on mismatched lengths
+    ///
+    /// # Arguments
+    /// * `v1` - First vector
+    /// * `v2` - Second vector
+    ///
+    /// # Returns
+    /// * `Result<f64>` - The distance, or error if the vectors have different lengths (DTW
+    ///   aligns series of any lengths and never fails)
+    pub fn try_distance(&self, v1: &[f64], v2: &[f64]) -> Result<f64> {
+        match self {
+            DistanceMetric::Euclidean => try_euclidean_distance(v1, v2),
+            DistanceMetric::Manhattan => try_manhattan_distance(v1, v2),
+            DistanceMetric::Cosine => {
+                // Same rule as `cosine_distance_matrix`: a zero vector is 1.0 away from every
+                // vector, even another zero vector
+                if v1 == v2 && v1.iter().any(|&x| x != 0.0) {
+                    return Ok(0.0);
+                }
+                Ok(1.0 - try_cosine_similarity(v1, v2)?)
+            }
+            DistanceMetric::Dtw { window } => Ok(dtw_distance(v1, v2, *window)),
+        }
+    }
+    
+    /// Whether this is a true metric that satisfies the triangle inequality
+    ///
+    /// Ball trees and other spatial indexes prune with the triangle inequality, so they only
+    /// return correct neighbors for Euclidean and Manhattan; Cosine and DTW need brute force.
+    pub fn is_metric(&self) -> bool {
+        matches!(self, DistanceMetric::Euclidean | DistanceMetric::Manhattan)
+    }
+}
+
+/// Compute Manhattan (L1) distance between two vectors
+///
+/// Panics if the vectors have different lengths; use `try_manhattan_distance` for data that
+/// has not been validated.
+///
+/// # Arguments
+/// * `v1` - First vector
+/// * `v2` - Second vector
+///
+/// # Returns
+/// * `f64` - Manhattan distance
+pub fn manhattan_distance(v1: &[f64], v2: &[f64]) -> f64 {
+    try_manhattan_distance(v1, v2).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Compute Manhattan (L1) distance between two vectors, failing on mismatched lengths
+///
+/// # Arguments
+/// * `v1` - First vector
+/// * `v2` - Second vector
+///
+/// # Returns
+/// * `Result<f64>` - Manhattan distance, or error if the vectors have different lengths
+pub fn try_manhattan_distance(v1: &[f64], v2: &[f64]) -> Result<f64> {
+    check_same_length(v1, v2)?;
+    
+    Ok(v1.iter().zip(v2.iter()).map(|(&a, &b)| (a - b).abs()).sum())
+}
+
+/// Compute the dynamic time warping (DTW) distance between two time series
+///
+/// Finds the monotone alignment of the two series that minimizes the summed absolute
+/// differences of aligned samples, so series with the same shape but shifted or stretched in
+/// time are close. Series may have different lengths. With a Sakoe-Chiba band, sample `i` may
+/// only be aligned with samples `j` where `|i - j| <= window`; the band is widened to the
+/// length difference if needed so that an alignment always exists. Runs in O(N·M) time, or
+/// O(N·window) with a band.
+///
+/// # Arguments
+/// * `a` - First time series
+/// * `b` - Second time series
+/// * `window` - Optional Sakoe-Chiba band half-width (default: unconstrained)
+///
+/// # Returns
+/// * `f64` - DTW distance (0.0 if both series are empty, infinity if only one is)
+pub fn dtw_distance(a: &[f64], b: &[f64], window: Option<usize>) -> f64 {
+    let (n, m) = (a.len(), b.len());
+    if n == 0 || m == 0 {
+        return if n == m { 0.0 } else { f64::INFINITY };
+    }
+    let window = window.unwrap_or(n.max(m)).max(n.abs_diff(m));
+    
+    // Two rows of the cumulative cost matrix; column 0 is the empty prefix of `b`
+    let mut prev = vec![f64::INFINITY; m + 1];
+    let mut curr = vec![f64::INFINITY; m + 1];
+    prev[0] = 0.0;
+    for i in 1..=n {
+        curr.fill(f64::INFINITY);
+        let start = i.saturating_sub(window).max(1);
+        let end = (i + window).min(m);
+        for j in start..=end {
+            let cost = (a[i - 1] - b[j - 1]).abs();
+            curr[j] = cost + prev[j].min(curr[j - 1]).min(prev[j - 1]);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    
+    prev[m]
+}
+
+/// Compute the Gower distance between two mixed-type vectors
+///
+/// Numeric features contribute their absolute difference divided by the feature's range,
+/// categorical features (e.g. category codes or one-hot columns) contribute 0 on a match and
+/// 1 on a mismatch, and the result is the average over features. This keeps many binary
+/// columns from dominating the distance as they do under Euclidean distance. Numeric features
+/// with a zero range contribute 0.
+///
+/// # Arguments
+/// * `v1` - First vector
+/// * `v2` - Second vector
+/// * `is_categorical` - Whether each feature is categorical
+/// * `ranges` - Range (max - min over the dataset) of each feature; ignored for categorical
+///   features (see `feature_ranges`)
+///
+/// # Returns
+/// * `Result<f64>` - Gower distance (0 to 1), or error if the vectors, `is_categorical` and
+///   `ranges` differ in length
+pub fn gower_distance(v1: &[f64], v2: &[f64], is_categorical: &[bool], ranges: &[f64]) -> Result<f64> {
+    check_same_length(v1, v2)?;
+    if is_categorical.len() != v1.len() || ranges.len() != v1.len() {
+        return Err(anyhow!(
+            "Vectors have {} features but is_categorical has {} and ranges has {}",
+            v1.len(),
+            is_categorical.len(),
+            ranges.len()
+        ));
+    }
+    if v1.is_empty() {
+        return Ok(0.0);
+    }
+    
+    let total: f64 = (0..v1.len())
+        .map(|i| {
+            if is_categorical[i] {
+                if v1[i] == v2[i] { 0.0 } else { 1.0 }
+            } else if ranges[i] > 0.0 {
+                ((v1[i] - v2[i]).abs() / ranges[i]).min(1.0)
+            } else {
+                0.0
+            }
+        })
+        .sum();
+    
+    Ok(total / v1.len() as f64)
+}
+
+/// Compute the range (max - min) of every feature of a dataset
+///
+/// # Arguments
+/// * `data` - The data points
+///
+/// # Returns
+/// * `Vec<f64>` - Range per feature (empty if `data` is empty)
+pub fn feature_ranges(data: &[Vec<f64>]) -> Vec<f64> {
+    if data.is_empty() {
+        return Vec::new();
+    }
+    
+    (0..data[0].len())
+        .map(|dim| {
+            let (min, max) = data.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                (min.min(v[dim]), max.max(v[dim]))
+            });
+            max - min
+        })
+        .collect()
+}
+
+/// Compute the full pairwise distance matrix of a set of vectors
+///
+/// Allocates an N×N matrix, i.e. 8·N² bytes. Panics if the vectors have different lengths.
+///
+/// # Arguments
+/// * `data` - The vectors
+/// * `metric` - The distance metric to use
+///
+/// # Returns
+/// * `Array2<f64>` - Symmetric N×N distance matrix with a zero diagonal
+pub fn pairwise_distances(data: &[Vec<f64>], metric: DistanceMetric) -> Array2<f64> {
+    if metric == DistanceMetric::Cosine {
+        return cosine_distance_matrix(data).unwrap_or_else(|e| panic!("{}", e));
+    }
+    
+    let n = data.len();
+    let mut distances = Array2::zeros((n, n));
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let d = metric.distance(&data[i], &data[j]);
+            distances[[i, j]] = d;
+            distances[[j, i]] = d;
+        }
+    }
+    distances
+}
+
+/// A rectangular block of a pairwise distance matrix
+#[derive(Debug, Clone)]
+pub struct DistanceTile {
+    /// Index of the first data point covered by the tile's rows
+    pub row_start: usize,
+    /// Index of the first data point covered by the tile's columns
+    pub col_start: usize,
+    /// Distances, where entry `[i, j]` is the distance between points `row_start + i` and `col_start + j`
+    pub distances: Array2<f64>,
+}
+
+/// Iterator over the tiles of a pairwise distance matrix, see `pairwise_distances_tiled`
+pub struct PairwiseDistanceTiles<'a> {
+    data: &'a [Vec<f64>],
+    metric: DistanceMetric,
+    tile_size: usize,
+    row_start: usize,
+    col_start: usize,
+}
+
+impl Iterator for PairwiseDistanceTiles<'_> {
+    type Item = DistanceTile;
+    
+    fn next(&mut self) -> Option<DistanceTile> {
+        let n = self.data.len();
+        if self.row_start >= n {
+            return None;
+        }
+        
+        let row_end = (self.row_start + self.tile_size).min(n);
+        let col_end = (self.col_start + self.tile_size).min(n);
+        let distances = Array2::from_shape_fn((row_end - self.row_start, col_end - self.col_start), |(i, j)| {
+            self.metric.distance(&self.data[self.row_start + i], &self.data[self.col_start + j])
+        });
+        let tile = DistanceTile {
+            row_start: self.row_start,
+            col_start: self.col_start,
+            distances,
+        };
+        
+        self.col_start = col_end;
+        if self.col_start >= n {
+            self.col_start = 0;
+            self.row_start = row_end;
+        }
+        Some(tile)
+    }
+}
+
+/// Compute the pairwise distance matrix block by block
+///
+/// Yields the full N×N matrix as tiles of at most `tile_size`×`tile_size` entries, in row-major
+/// tile order. Only one tile is held in memory at a time, i.e. 8·`tile_size`² bytes (a tile
+/// size of 1024 needs 8 MiB) instead of the 8·N² bytes of `pairwise_distances`, which lets
+/// distance-based metrics run on datasets whose full matrix would not fit in memory.
+///
+/// # Arguments
+/// * `data` - The vectors
+/// * `metric` - The distance metric to use
+/// * `tile_size` - Maximum number of rows and columns per tile (at least 1)
+///
+/// # Returns
+/// * `PairwiseDistanceTiles` - Iterator over the distance tiles
+pub fn pairwise_distances_tiled(
+    data: &[Vec<f64>],
+    metric: DistanceMetric,
+    tile_size: usize,
+) -> PairwiseDistanceTiles<'_> {
+    PairwiseDistanceTiles {
+        data,
+        metric,
+        tile_size: tile_size.max(1),
+        row_start: 0,
+        col_start: 0,
+    }
+}
+
+/// Compute the pairwise cosine distance matrix of a set of vectors
+///
+/// Row norms are computed once and the similarities are obtained with a single matrix
+/// product, which is much faster than calling `cosine_similarity` for every pair.
+/// Zero vectors have distance 1.0 to every other vector. The diagonal is always 0.
+///
+/// # Arguments
+/// * `data` - The vectors
+///
+/// # Returns
+/// * `Result<Array2<f64>>` - Symmetric N×N matrix of `1 - cosine_similarity` values (range 0
+///   to 2), or error if the vectors have different lengths
+pub fn cosine_distance_matrix(data: &[Vec<f64>]) -> Result<Array2<f64>> {
+    Ok(cosine_distance_matrix_normalized(&Normalized::new(data)?))
+}
+
+/// Compute the pairwise cosine distance matrix of pre-normalized vectors
+///
+/// Same as `cosine_distance_matrix`, without recomputing the row norms.
+///
+/// # Arguments
+/// * `data` - The vectors with their precomputed norms
+///
+/// # Returns
+/// * `Array2<f64>` - Symmetric N×N matrix of `1 - cosine_similarity` values (range 0 to 2)
+pub fn cosine_distance_matrix_normalized(data: &Normalized) -> Array2<f64> {
+    let n = data.len();
+    let unit = data.unit_rows();
+    
+    let mut distances = unit.dot(&unit.t()).mapv(|sim| (1.0 - sim).clamp(0.0, 2.0));
+    for i in 0..n {
+        for j in 0..n {
+            if i == j {
+                distances[[i, j]] = 0.0;
+            } else if data.norms[i] == 0.0 || data.norms[j] == 0.0 {
+                distances[[i, j]] = 1.0;
+            }
+        }
+    }
+    
+    distances
+}
+
+/// Vectors stored together with their L2 norms, for repeated cosine-based computations
+///
+/// Build it once with `Normalized::new` and pass it to the `*_normalized` functions and to
+/// `spherical_kmeans` to avoid recomputing the O(N·D) norms on every call. Rows are stored
+/// scaled to unit length; zero vectors stay zero.
+#[derive(Debug, Clone)]
+pub struct Normalized {
+    unit: Array2<f64>,
+    norms: Vec<f64>,
+}
+
+impl Normalized {
+    /// Computes the norms of `data` and stores its rows scaled to unit length
+    ///
+    /// # Returns
+    /// * `Result<Normalized>` - The normalized rows, or error if they differ in length
+    pub fn new(data: &[Vec<f64>]) -> Result<Self> {
+        let mut unit = vec_to_array2(data)?;
+        let mut norms = Vec::with_capacity(data.len());
+        for mut row in unit.outer_iter_mut() {
+            let norm = row.dot(&row).sqrt();
+            if norm > 0.0 {
+                row /= norm;
+            }
+            norms.push(norm);
+        }
+        Ok(Normalized { unit, norms })
+    }
+    
+    /// The rows scaled to unit length (zero rows stay zero)
+    pub fn unit_rows(&self) -> &Array2<f64> {
+        &self.unit
+    }
+    
+    /// The L2 norm of every original row
+    pub fn norms(&self) -> &[f64] {
+        &self.norms
+    }
+    
+    /// Number of rows
+    pub fn len(&self) -> usize {
+        self.norms.len()
+    }
+    
+    /// Whether there are no rows
+    pub fn is_empty(&self) -> bool {
+        self.norms.is_empty()
+    }
+}
+
+/// Compute the cosine similarity of every query vector with every reference vector
+///
+/// Norms are computed once per vector and all similarities come from a single matrix
+/// product, which is much faster than calling `cosine_similarity` per pair for batch
+/// retrieval. Rows or columns of zero vectors are 0 rather than NaN.
+///
+/// # Arguments
+/// * `queries` - The query vectors
+/// * `refs` - The reference vectors (e.g. cluster centroids), of the same dimension
+///
+/// # Returns
+/// * `Result<Array2<f64>>` - Q×R matrix whose entry `[q, r]` is the similarity of `queries[q]`
+///   and `refs[r]`, or error if the rows of either input differ in length or the two inputs
+///   differ in dimension
+pub fn cosine_similarity_matrix(queries: &[Vec<f64>], refs: &[Vec<f64>]) -> Result<Array2<f64>> {
+    let query_dim = validate_row_lengths(queries)?;
+    let ref_dim = validate_row_lengths(refs)?;
+    if queries.is_empty() || refs.is_empty() {
+        return Ok(Array2::zeros((queries.len(), refs.len())));
+    }
+    if query_dim != ref_dim {
+        return Err(anyhow!("Queries have {} dimensions but references have {}", query_dim, ref_dim));
+    }
     
-    dot_product / (mag1 * mag2)
-} 
\ No newline at end of file
+    let queries = Normalized::new(queries)?;
+    let refs = Normalized::new(refs)?;
+    Ok(queries.unit_rows().dot(&refs.unit_rows().t()).mapv(|sim| sim.clamp(-1.0, 1.0)))
+}
+
+/// A sparse vector stored as `(dimension index, value)` pairs
+///
+/// Indices must be sorted in ascending order without duplicates; zero entries may be omitted.
+/// Each stored entry takes 16 bytes against 8 bytes per dense entry, so sparse storage saves
+/// memory below roughly 50% density, and dot products and distances are typically faster
+/// below 10-20% density (e.g. bag-of-words or TF-IDF features).
+pub type SparseVector = Vec<(usize, f64)>;
+
+/// Convert a dense vector to a sparse vector, dropping zero entries
+///
+/// # Arguments
+/// * `dense` - The dense vector
+///
+/// # Returns
+/// * `SparseVector` - The nonzero entries with their indices
+pub fn to_sparse(dense: &[f64]) -> SparseVector {
+    dense
+        .iter()
+        .enumerate()
+        .filter(|(_, &value)| value != 0.0)
+        .map(|(idx, &value)| (idx, value))
+        .collect()
+}
+
+/// Compute the dot product of two sparse vectors, visiting only nonzero entries
+///
+/// # Arguments
+/// * `v1` - First sparse vector
+/// * `v2` - Second sparse vector
+///
+/// # Returns
+/// * `f64` - Dot product
+pub fn sparse_dot(v1: &[(usize, f64)], v2: &[(usize, f64)]) -> f64 {
+    let (mut i, mut j) = (0, 0);
+    let mut dot = 0.0;
+    while i < v1.len() && j < v2.len() {
+        match v1[i].0.cmp(&v2[j].0) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                dot += v1[i].1 * v2[j].1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    dot
+}
+
+/// Compute the L2 norm of a sparse vector
+pub fn sparse_norm(v: &[(usize, f64)]) -> f64 {
+    v.iter().map(|&(_, x)| x * x).sum::<f64>().sqrt()
+}
+
+/// Compute Euclidean distance between two sparse vectors
+///
+/// # Arguments
+/// * `v1` - First sparse vector
+/// * `v2` - Second sparse vector
+///
+/// # Returns
+/// * `f64` - Euclidean distance
+pub fn sparse_euclidean_distance(v1: &[(usize, f64)], v2: &[(usize, f64)]) -> f64 {
+    let squared = sparse_dot(v1, v1) - 2.0 * sparse_dot(v1, v2) + sparse_dot(v2, v2);
+    squared.max(0.0).sqrt()
+}
+
+/// Compute cosine similarity between two sparse vectors
+///
+/// # Arguments
+/// * `v1` - First sparse vector
+/// * `v2` - Second sparse vector
+///
+/// # Returns
+/// * `f64` - Cosine similarity (-1 to 1), or 0 if either vector is zero
+pub fn sparse_cosine_similarity(v1: &[(usize, f64)], v2: &[(usize, f64)]) -> f64 {
+    let magnitude = sparse_norm(v1) * sparse_norm(v2);
+    if magnitude == 0.0 {
+        return 0.0;
+    }
+    sparse_dot(v1, v2) / magnitude
+}
+
+/// Compute the mean vector (per-dimension average) of a set of data points
+///
+/// # Arguments
+/// * `data` - The data points
+///
+/// # Returns
+/// * `Vec<f64>` - Mean vector (empty if `data` is empty)
+pub fn global_mean(data: &[Vec<f64>]) -> Vec<f64> {
+    let indices: Vec<usize> = (0..data.len()).collect();
+    mean_of_rows(data, &indices)
+}
+
+/// Compute the mean vector of the data points at the given indices
+pub(crate) fn mean_of_rows(data: &[Vec<f64>], indices: &[usize]) -> Vec<f64> {
+    if indices.is_empty() {
+        return Vec::new();
+    }
+    
+    let mut mean = vec![0.0; data[indices[0]].len()];
+    for &idx in indices {
+        for (m, &x) in mean.iter_mut().zip(data[idx].iter()) {
+            *m += x;
+        }
+    }
+    
+    let count = indices.len() as f64;
+    for m in mean.iter_mut() {
+        *m /= count;
+    }
+    mean
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn cosine_distance_of_zero_vectors_matches_matrix() {
+        let data = vec![vec![0.0, 0.0], vec![0.0, 0.0], vec![1.0, 0.0], vec![2.0, 0.0]];
+        let matrix = pairwise_distances(&data, DistanceMetric::Cosine);
+        for i in 0..data.len() {
+            for j in 0..data.len() {
+                if i != j {
+                    let d = DistanceMetric::Cosine.distance(&data[i], &data[j]);
+                    assert!((d - matrix[[i, j]]).abs() < 1e-12, "points {} and {}", i, j);
+                }
+            }
+        }
+        assert_eq!(DistanceMetric::Cosine.distance(&data[0], &data[1]), 1.0);
+        assert_eq!(DistanceMetric::Cosine.distance(&data[2], &data[2]), 0.0);
+    }
+    
+    #[test]
+    fn cosine_similarity_matrix_validates_every_row() {
+        let queries = vec![vec![1.0, 0.0], vec![0.0, 0.0]];
+        let refs = vec![vec![2.0, 0.0], vec![0.0, 3.0], vec![-1.0, 0.0]];
+        let similarities = cosine_similarity_matrix(&queries, &refs).unwrap();
+        assert_eq!(similarities.row(0).to_vec(), vec![1.0, 0.0, -1.0]);
+        assert_eq!(similarities.row(1).to_vec(), vec![0.0, 0.0, 0.0]);
+        
+        let ragged = vec![vec![1.0, 0.0], vec![1.0, 0.0, 0.0]];
+        assert!(cosine_similarity_matrix(&ragged, &refs).is_err());
+        assert!(cosine_similarity_matrix(&queries, &ragged).is_err());
+        assert!(cosine_similarity_matrix(&queries, &[vec![1.0, 0.0, 0.0]]).is_err());
+    }
+    
+    #[test]
+    fn normalized_rejects_ragged_rows() {
+        let normalized = Normalized::new(&[vec![3.0, 4.0], vec![0.0, 0.0]]).unwrap();
+        assert_eq!(normalized.norms(), &[5.0, 0.0]);
+        assert_eq!(normalized.unit_rows().row(0).to_vec(), vec![0.6, 0.8]);
+        
+        let error = Normalized::new(&[vec![3.0, 4.0], vec![1.0]]).unwrap_err();
+        assert_eq!(error.to_string(), "Row 1 has length 1, expected 2");
+        assert!(cosine_distance_matrix(&[vec![3.0, 4.0], vec![1.0]]).is_err());
+    }
+    
+    #[test]
+    fn vec_to_array2_rejects_ragged_rows() {
+        assert_eq!(vec_to_array2(&[]).unwrap().dim(), (0, 0));
+        assert_eq!(vec_to_array2(&[vec![1.0, 2.0], vec![3.0, 4.0]]).unwrap()[[1, 0]], 3.0);
+        let error = vec_to_array2(&[vec![1.0, 2.0], vec![3.0, 4.0], vec![5.0]]).unwrap_err();
+        assert_eq!(error.to_string(), "Row 2 has length 1, expected 2");
+    }
+    
+    #[test]
+    fn try_distance_reports_mismatched_lengths() {
+        let (a, b) = ([1.0, 2.0], [4.0, -2.0]);
+        assert_eq!(try_manhattan_distance(&a, &b).unwrap(), 7.0);
+        assert_eq!(DistanceMetric::Euclidean.try_distance(&a, &b).unwrap(), 5.0);
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::Manhattan, DistanceMetric::Cosine] {
+            let error = metric.try_distance(&a, &[1.0]).unwrap_err();
+            assert_eq!(error.to_string(), "Vectors must have the same length, got 2 and 1");
+        }
+        assert_eq!(DistanceMetric::Dtw { window: None }.try_distance(&a, &[1.0, 2.0, 2.0]).unwrap(), 0.0);
+    }
+    
+    #[test]
+    fn gower_distance_checks_lengths() {
+        let (is_categorical, ranges) = ([false, true], [4.0, 0.0]);
+        assert_eq!(gower_distance(&[1.0, 2.0], &[3.0, 5.0], &is_categorical, &ranges).unwrap(), 0.75);
+        assert_eq!(gower_distance(&[1.0, 2.0], &[1.0, 2.0], &is_categorical, &ranges).unwrap(), 0.0);
+        assert!(gower_distance(&[1.0, 2.0], &[1.0], &is_categorical, &ranges).is_err());
+        let error = gower_distance(&[1.0, 2.0], &[1.0, 2.0], &[false], &ranges).unwrap_err();
+        assert_eq!(error.to_string(), "Vectors have 2 features but is_categorical has 1 and ranges has 2");
+    }
+    
+    #[test]
+    fn dtw_distance_aligns_shifted_series() {
+        assert_eq!(dtw_distance(&[1.0, 2.0, 3.0], &[1.0, 2.0, 3.0], None), 0.0);
+        assert_eq!(dtw_distance(&[0.0, 0.0, 1.0, 2.0], &[0.0, 1.0, 2.0], None), 0.0);
+        assert_eq!(dtw_distance(&[1.0, 2.0], &[3.0], None), 3.0);
+        assert_eq!(dtw_distance(&[], &[], None), 0.0);
+        assert_eq!(dtw_distance(&[1.0], &[], None), f64::INFINITY);
+        
+        // A one-step shift is free without a band, but not with a zero-width band
+        let (a, b) = ([0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(dtw_distance(&a, &b, None), 0.0);
+        assert_eq!(dtw_distance(&a, &b, Some(1)), 0.0);
+        assert_eq!(dtw_distance(&a, &b, Some(0)), 2.0);
+        assert_eq!(dtw_distance(&b, &a, Some(0)), 2.0);
+        
+        // The band is widened to the length difference so an alignment exists
+        assert_eq!(dtw_distance(&[1.0, 2.0, 3.0], &[1.0, 3.0], Some(0)), 1.0);
+    }
+}